@@ -0,0 +1,327 @@
+use std::{error::Error, fmt};
+
+use super::{
+    opcodes::Instruction,
+    info::InstructionInfo,
+    decoder::{build_table, build_prefix},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AssemblerError {
+    /// Nothing to assemble
+    Empty,
+
+    /// No instruction with such mnemonic
+    UnknownMnemonic(String),
+
+    /// Mnemonic exists, but not with these operands
+    InvalidOperands(String),
+
+    /// Immediate operand doesn't fit into the instruction
+    OutOfRange { operand: String, min: i32, max: i32 },
+
+    /// Relative jump destination is further than -128..127 bytes
+    JumpOutOfRange { origin: u16, target: u16 },
+}
+
+impl fmt::Display for AssemblerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssemblerError::Empty => write!(f, "empty instruction"),
+            AssemblerError::UnknownMnemonic(m) => write!(f, "unknown mnemonic {}", m),
+            AssemblerError::InvalidOperands(text) => write!(f, "invalid operands: {}", text),
+            AssemblerError::OutOfRange { operand, min, max } =>
+                write!(f, "operand {} is out of range {}..{}", operand, min, max),
+            AssemblerError::JumpOutOfRange { origin, target } =>
+                write!(f, "JR from ${:04X} can't reach ${:04X}", origin, target),
+        }
+    }
+}
+
+impl Error for AssemblerError {}
+
+/// Immediate operand placeholders of `Instruction::to_string`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Placeholder {
+    U8,
+    U16,
+    I8,
+}
+
+/// Operand of a template - literal text or immediate surrounded by `prefix` and `suffix`
+#[derive(Clone, Debug)]
+enum Operand {
+    Literal(String),
+    Immediate { prefix: String, kind: Placeholder, suffix: String },
+}
+
+impl Operand {
+    fn parse(token: &str) -> Operand {
+        let placeholders = [
+            ("a16", Placeholder::U16),
+            ("u16", Placeholder::U16),
+            ("u8", Placeholder::U8),
+            ("i8", Placeholder::I8),
+        ];
+        for (name, kind) in placeholders {
+            if let Some(at) = token.find(name) {
+                let (mut prefix, mut suffix) = (token[..at].to_string(), token[at + name.len()..].to_string());
+                if name == "a16" {
+                    prefix = "[".to_string();
+                    suffix = "]".to_string();
+                }
+                // `SP+i8` and `[$FF00+u8]` - sign belongs to the number
+                if prefix.ends_with('+') {
+                    prefix.pop();
+                }
+                return Operand::Immediate { prefix, kind, suffix };
+            }
+        }
+        Operand::Literal(token.to_uppercase())
+    }
+}
+
+/// Instruction encoding with its textual form split into tokens
+struct Template {
+    mnemonic: String,
+    operands: Vec<Operand>,
+    opcode: Vec<u8>,
+    info: InstructionInfo,
+}
+
+/// Parses `$FF`, `0xFF`, `255`, with optional sign
+fn parse_number(text: &str) -> Option<i32> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let value = if let Some(hex) = text.strip_prefix('$').or_else(|| text.strip_prefix("0X")) {
+        i32::from_str_radix(hex, 16).ok()?
+    } else {
+        text.parse::<i32>().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// Rewrites rgbasm forms(`LDH [$FF44] A`, `LD A [HL+]`, `LDH A [C]`) to the
+/// `Instruction::to_string` ones, other instructions are kept as they are
+fn rgbasm_alias(mnemonic: &str, operands: Vec<&str>) -> (String, Vec<String>) {
+    let mut mnemonic = mnemonic.to_string();
+    let operands = operands
+        .into_iter()
+        .map(|operand| match operand {
+            "[HL+]" | "[HLI]" if mnemonic == "LD" => {
+                mnemonic = "LDI".to_string();
+                "[HL]".to_string()
+            },
+            "[HL-]" | "[HLD]" if mnemonic == "LD" => {
+                mnemonic = "LDD".to_string();
+                "[HL]".to_string()
+            },
+            "[C]" if mnemonic == "LD" || mnemonic == "LDH" => "[$FF00+C]".to_string(),
+            _ if mnemonic == "LDH" => {
+                let address = operand.strip_prefix('[').and_then(|o| o.strip_suffix(']')).and_then(parse_number);
+                match address {
+                    Some(addr @ 0xFF00..=0xFFFF) => format!("[$FF00+${:02X}]", addr & 0xFF),
+                    _ => operand.to_string(),
+                }
+            },
+            _ => operand.to_string(),
+        })
+        .collect();
+    if mnemonic == "LDH" {
+        mnemonic = "LD".to_string();
+    }
+    (mnemonic, operands)
+}
+
+/// ## SM83 assembler
+/// Encodes a single instruction written in `Instruction::to_string` syntax(`LD A [HL]`,
+/// `JP NZ $1234`, `LD [$FF00+$44] A`...) using the opcode tables. Commas are allowed
+/// between operands and case doesn't matter. `JR` takes an absolute destination,
+/// the same way disassembly shows it. rgbasm spellings of `LDH`, `LDI` and `LDD`
+/// are accepted as well.
+pub struct Assembler {
+    templates: Vec<Template>,
+}
+
+impl Default for Assembler {
+    fn default() -> Self {
+        Assembler::new()
+    }
+}
+
+impl Assembler {
+    pub fn new() -> Assembler {
+        let opcodes = build_table().iter().enumerate().map(|(op, info)| (vec![op as u8], *info)).collect::<Vec<_>>();
+        let prefixed = build_prefix().iter().enumerate().map(|(op, info)| (vec![0xCB, op as u8], *info)).collect::<Vec<_>>();
+
+        let templates = opcodes
+            .into_iter()
+            .chain(prefixed)
+            .filter(|(_, info)| !matches!(info.instruction, Instruction::PREFIX | Instruction::XXX))
+            .map(|(opcode, info)| {
+                let text = info.instruction.to_string();
+                let mut tokens = text.split_whitespace();
+                let mnemonic = tokens.next().unwrap_or_default().to_string();
+                Template {
+                    mnemonic,
+                    operands: tokens.map(Operand::parse).collect(),
+                    opcode,
+                    info,
+                }
+            })
+            .collect();
+        Assembler { templates }
+    }
+
+    /// Encodes `text` as an instruction placed at `origin`
+    pub fn assemble(&self, text: &str, origin: u16) -> Result<Vec<u8>, AssemblerError> {
+        let text = text.replace(',', " ").to_uppercase();
+        let mut tokens = text.split_whitespace();
+        let mnemonic = tokens.next().ok_or(AssemblerError::Empty)?;
+        let (mnemonic, operands) = rgbasm_alias(mnemonic, tokens.collect());
+        let mnemonic = mnemonic.as_str();
+        let operands: Vec<&str> = operands.iter().map(String::as_str).collect();
+
+        let candidates: Vec<&Template> = self.templates.iter().filter(|t| t.mnemonic == mnemonic).collect();
+        if candidates.is_empty() {
+            return Err(AssemblerError::UnknownMnemonic(mnemonic.to_string()));
+        }
+
+        // error of the closest candidate, reported if nothing matches
+        let mut error = AssemblerError::InvalidOperands(format!("{} {}", mnemonic, operands.join(" ")));
+        for template in candidates.iter().filter(|t| t.operands.len() == operands.len()) {
+            match self.encode(template, &operands, origin) {
+                Some(Ok(bytes)) => return Ok(bytes),
+                Some(Err(e)) => error = e,
+                None => {},
+            }
+        }
+        Err(error)
+    }
+
+    /// Encodes `operands` with `template`. `None` if they don't match it,
+    /// error if they do but the immediate value doesn't fit
+    fn encode(&self, template: &Template, operands: &[&str], origin: u16) -> Option<Result<Vec<u8>, AssemblerError>> {
+        let mut bytes = template.opcode.clone();
+        for (expected, actual) in template.operands.iter().zip(operands) {
+            match expected {
+                Operand::Literal(literal) => {
+                    let same = literal == actual || matches!(
+                        (parse_number(literal), parse_number(actual)),
+                        (Some(a), Some(b)) if a == b
+                    );
+                    if !same {
+                        return None;
+                    }
+                },
+                Operand::Immediate { prefix, kind, suffix } => {
+                    let number = actual.strip_prefix(prefix.as_str())?.strip_suffix(suffix.as_str())?;
+                    let value = parse_number(number)?;
+                    match self.immediate(template, *kind, value, actual, origin) {
+                        Ok(imm) => bytes.extend(imm),
+                        Err(e) => return Some(Err(e)),
+                    }
+                },
+            }
+        }
+        // STOP is followed by a byte that is skipped
+        bytes.resize(template.info.length as usize, 0x00);
+        Some(Ok(bytes))
+    }
+
+    /// Bytes of immediate operand `value`
+    fn immediate(
+        &self, template: &Template, kind: Placeholder, value: i32, text: &str, origin: u16,
+    ) -> Result<Vec<u8>, AssemblerError> {
+        let out_of_range = |min, max| AssemblerError::OutOfRange { operand: text.to_string(), min, max };
+        match kind {
+            Placeholder::U8 if (0..=0xFF).contains(&value) => Ok(vec![value as u8]),
+            Placeholder::U8 => Err(out_of_range(0, 0xFF)),
+            Placeholder::U16 if (0..=0xFFFF).contains(&value) => Ok((value as u16).to_le_bytes().to_vec()),
+            Placeholder::U16 => Err(out_of_range(0, 0xFFFF)),
+            Placeholder::I8 => match template.info.instruction {
+                Instruction::JR(_, _) => {
+                    if !(0..=0xFFFF).contains(&value) {
+                        return Err(out_of_range(0, 0xFFFF));
+                    }
+                    let next = origin as i32 + template.info.length as i32;
+                    let offset = value - next;
+                    if !(-128..=127).contains(&offset) {
+                        return Err(AssemblerError::JumpOutOfRange { origin, target: value as u16 });
+                    }
+                    Ok(vec![offset as i8 as u8])
+                },
+                // both signed and raw byte forms are accepted
+                _ if (-128..=0xFF).contains(&value) => Ok(vec![value as u8]),
+                _ => Err(out_of_range(-128, 0xFF)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::{Disassembler, Location};
+
+    #[test]
+    fn test_round_trip() {
+        let asm = Assembler::new();
+        let disasm = Disassembler::new();
+        let origin = Location::new(1, 0x4000);
+        let opcodes = build_table().iter().enumerate().map(|(op, info)| (vec![op as u8], *info)).collect::<Vec<_>>();
+        let prefixed = build_prefix().iter().enumerate().map(|(op, info)| (vec![0xCB, op as u8], *info)).collect::<Vec<_>>();
+
+        for (opcode, info) in opcodes.into_iter().chain(prefixed) {
+            if matches!(info.instruction, Instruction::PREFIX | Instruction::XXX) {
+                continue;
+            }
+            for imm in [[0x00, 0x00], [0x7F, 0x12], [0xFE, 0xC0], [0x80, 0xFF]] {
+                let mut bytes = opcode.clone();
+                bytes.extend_from_slice(&imm);
+                bytes.truncate(info.length as usize);
+                if let Instruction::STOP = info.instruction {
+                    bytes[1] = 0x00;
+                }
+
+                let text = disasm.decode_bytes(origin, &bytes).unwrap().text();
+                assert_eq!(asm.assemble(&text, origin.addr), Ok(bytes), "{}", text);
+            }
+        }
+    }
+
+    #[test]
+    fn test_syntax() {
+        let asm = Assembler::new();
+        assert_eq!(asm.assemble("ld a, [hl]", 0), Ok(vec![0x7E]));
+        assert_eq!(asm.assemble("LD [$FF00+C] A", 0), Ok(vec![0xE2]));
+        assert_eq!(asm.assemble("LD HL SP-2", 0), Ok(vec![0xF8, 0xFE]));
+        assert_eq!(asm.assemble("add sp, -2", 0), Ok(vec![0xE8, 0xFE]));
+        assert_eq!(asm.assemble("rst $38", 0), Ok(vec![0xFF]));
+        assert_eq!(asm.assemble("jr $0100", 0x0100), Ok(vec![0x18, 0xFE]));
+        assert_eq!(asm.assemble("call nz 0x1234", 0), Ok(vec![0xC4, 0x34, 0x12]));
+        // rgbasm
+        assert_eq!(asm.assemble("ldh [$FF44], a", 0), Ok(vec![0xE0, 0x44]));
+        assert_eq!(asm.assemble("ldh a, [c]", 0), Ok(vec![0xF2]));
+        assert_eq!(asm.assemble("ld [hl+], a", 0), Ok(vec![0x22]));
+        assert_eq!(asm.assemble("ld a, [hld]", 0), Ok(vec![0x3A]));
+    }
+
+    #[test]
+    fn test_errors() {
+        let asm = Assembler::new();
+        assert_eq!(asm.assemble("  ", 0), Err(AssemblerError::Empty));
+        assert_eq!(asm.assemble("MOV A B", 0), Err(AssemblerError::UnknownMnemonic("MOV".to_string())));
+        assert_eq!(asm.assemble("LD [BC] B", 0), Err(AssemblerError::InvalidOperands("LD [BC] B".to_string())));
+        assert_eq!(
+            asm.assemble("LD A $100", 0),
+            Err(AssemblerError::OutOfRange { operand: "$100".to_string(), min: 0, max: 0xFF })
+        );
+        assert_eq!(
+            asm.assemble("JR $0200", 0x0100),
+            Err(AssemblerError::JumpOutOfRange { origin: 0x0100, target: 0x0200 })
+        );
+    }
+}
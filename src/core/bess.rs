@@ -0,0 +1,313 @@
+use std::{fs, path::Path};
+
+use crate::core::state::{MachineState, Reader, StateError, rom_hash, write_block};
+
+/*
+    Best Effort Save State(BESS)
+
+    Format shared by SameBoy and other emulators, appended to their own save
+    states. The file ends with an 8 byte footer: u32 offset of the first block
+    from the start of the file, then "BESS". Blocks use the same layout as ours,
+    4 byte ASCII tag, u32 length, payload, numbers are little endian.
+
+    Blocks, CORE has to be the first one or follow NAME:
+        "NAME" - emulator that wrote the file, optional
+        "CORE" - required, 0xD0 bytes:
+                 u16 major(1) and minor version, 4 byte model("GDB " for DMG-B),
+                 PC AF BC DE HL SP(u16 each), IME, IE, execution state
+                 (0 running, 1 halted, 2 stopped), reserved byte,
+                 I/O registers 0xFF00-0xFF7F,
+                 size and offset(u32 each) of RAM, VRAM, MBC RAM, OAM, HRAM,
+                 background and object palette buffers elsewhere in the file
+        "INFO" - cartridge title(0x10 bytes) and global checksum, optional
+        "MBC " - u16 address and u8 value writes that restore the mapper
+        "RTC " - MBC3 clock, 0x30 bytes
+        "END " - empty, always last
+    Other blocks are skipped.
+
+    Without a mapper or RTC their blocks can't be applied and are reported as
+    ignored. Timer counters aren't part of BESS, they start from 0. Echo RAM
+    isn't stored either, it's mirrored from WRAM like writes keep it.
+*/
+
+const FOOTER: &[u8; 4] = b"BESS";
+const MAJOR_VERSION: u16 = 1;
+const MINOR_VERSION: u16 = 1;
+const CORE_SIZE: usize = 0xD0;
+const RTC_SIZE: usize = 0x30;
+const MODEL: &[u8; 4] = b"GDB ";
+
+/// Header fields identifying the cartridge in INFO
+const TITLE: std::ops::Range<usize> = 0x0134..0x0144;
+const GLOBAL_CHECKSUM: std::ops::Range<usize> = 0x014E..0x0150;
+const RAM_SIZE: usize = 0x0149;
+
+/// Echo RAM, mirrors the start of WRAM
+const ECHO: std::ops::Range<usize> = 0xE000..0xFE00;
+const WRAM: usize = 0xC000;
+
+/// Memory areas of CORE buffers in file order, palettes are CGB only and left empty
+const BUFFERS: [(&str, u16, usize); 5] = [
+    ("RAM", 0xC000, 0x2000),
+    ("VRAM", 0x8000, 0x2000),
+    ("MBC RAM", 0xA000, 0x2000),
+    ("OAM", 0xFE00, 0xA0),
+    ("HRAM", 0xFF80, 0x7F),
+];
+
+/// Machine state read from a BESS file
+#[derive(Clone, Debug)]
+pub struct Import {
+    pub state: MachineState,
+    /// Emulator that wrote the file, from NAME
+    pub emulator: Option<String>,
+    pub model: String,
+    /// Blocks that were read but can't be applied
+    pub ignored: Vec<String>,
+}
+
+/// Whether `data` ends with a BESS footer
+pub fn is_bess(data: &[u8]) -> bool {
+    data.ends_with(FOOTER) && data.len() >= 8
+}
+
+/// Size of cartridge RAM mapped to 0xA000, from the header
+fn cart_ram_size(memory: &[u8]) -> usize {
+    match memory[RAM_SIZE] {
+        0 => 0,
+        1 => 0x800,
+        _ => 0x2000,
+    }
+}
+
+pub fn export(state: &MachineState) -> Vec<u8> {
+    let memory = &state.memory;
+    let mut out = Vec::new();
+    let mut buffers = Vec::new();
+    for (name, start, size) in BUFFERS {
+        let size = if name == "MBC RAM" { cart_ram_size(memory) } else { size };
+        let start = start as usize;
+        buffers.push((size as u32, out.len() as u32));
+        out.extend_from_slice(&memory[start..start + size]);
+    }
+    let first_block = out.len() as u32;
+
+    // CORE has to follow NAME, readers may reject it anywhere else
+    write_block(&mut out, b"NAME", format!("gb-emu {}", env!("CARGO_PKG_VERSION")).as_bytes());
+
+    let mut core = Vec::with_capacity(CORE_SIZE);
+    core.extend_from_slice(&MAJOR_VERSION.to_le_bytes());
+    core.extend_from_slice(&MINOR_VERSION.to_le_bytes());
+    core.extend_from_slice(MODEL);
+    for word in [state.pc, state.af, state.bc, state.de, state.hl, state.sp] {
+        core.extend_from_slice(&word.to_le_bytes());
+    }
+    let execution = if state.stopped { 2 } else if state.halted { 1 } else { 0 };
+    core.extend_from_slice(&[state.ime as u8, memory[0xFFFF], execution, 0]);
+    core.extend_from_slice(&memory[0xFF00..0xFF80]);
+    for (size, offset) in buffers {
+        core.extend_from_slice(&size.to_le_bytes());
+        core.extend_from_slice(&offset.to_le_bytes());
+    }
+    // palettes
+    core.extend_from_slice(&[0; 16]);
+    write_block(&mut out, b"CORE", &core);
+    let mut info = memory[TITLE].to_vec();
+    info.extend_from_slice(&memory[GLOBAL_CHECKSUM]);
+    write_block(&mut out, b"INFO", &info);
+    write_block(&mut out, b"END ", &[]);
+
+    out.extend_from_slice(&first_block.to_le_bytes());
+    out.extend_from_slice(FOOTER);
+    out
+}
+
+/// Reads BESS blocks of `data` into a state for the cartridge `rom`
+pub fn import(data: &[u8], rom: &[u8]) -> Result<Import, StateError> {
+    if !is_bess(data) {
+        return Err(StateError::Invalid("no BESS footer".to_string()));
+    }
+    let mut footer = Reader { tag: "footer", data: &data[data.len() - 8..] };
+    let offset = footer.u32()? as usize;
+    let mut reader = Reader { tag: "BESS", data: data.get(offset..data.len() - 8).unwrap_or_default() };
+
+    let mut memory = vec![0; 0x10000];
+    for (place, value) in memory[..0x8000].iter_mut().zip(rom) {
+        *place = *value;
+    }
+    let mut import = Import {
+        state: MachineState {
+            af: 0, bc: 0, de: 0, hl: 0, sp: 0, pc: 0, instruction_pc: 0,
+            ime: false, ei_pending: false, halted: false, stopped: false,
+            cycles: 0, div_cycles: 0, tima_cycles: 0,
+            rom_bank: 1,
+            rom_hash: rom_hash(rom),
+            rom_size: rom.len() as u32,
+            memory: Vec::new(),
+            other_vram: Vec::new(),
+            buttons: 0,
+        },
+        emulator: None,
+        model: String::new(),
+        ignored: Vec::new(),
+    };
+    let mut core_found = false;
+
+    loop {
+        reader.tag = "block header";
+        let t = reader.take(4)?;
+        let tag = [t[0], t[1], t[2], t[3]];
+        let len = reader.u32()? as usize;
+        let name = String::from_utf8_lossy(&tag).trim_end().to_string();
+        let mut block = Reader { tag: "block", data: reader.take(len)? };
+        match &tag {
+            b"END " => break,
+            b"NAME" => import.emulator = Some(String::from_utf8_lossy(block.data).to_string()),
+            b"INFO" => {
+                let title = block.take(TITLE.len())?;
+                let checksum = block.take(GLOBAL_CHECKSUM.len())?;
+                if title != &memory[TITLE] || checksum != &memory[GLOBAL_CHECKSUM] {
+                    return Err(StateError::WrongRom);
+                }
+            },
+            b"CORE" => {
+                block.tag = "CORE";
+                if len < CORE_SIZE {
+                    return Err(StateError::Invalid("CORE block is too short".to_string()));
+                }
+                let major = block.u16()?;
+                if major != MAJOR_VERSION {
+                    return Err(StateError::UnsupportedVersion(major));
+                }
+                block.u16()?;
+                import.model = String::from_utf8_lossy(block.take(4)?).trim_end().to_string();
+                let state = &mut import.state;
+                state.pc = block.u16()?;
+                state.af = block.u16()?;
+                state.bc = block.u16()?;
+                state.de = block.u16()?;
+                state.hl = block.u16()?;
+                state.sp = block.u16()?;
+                state.instruction_pc = state.pc;
+                state.ime = block.u8()? != 0;
+                memory[0xFFFF] = block.u8()?;
+                match block.u8()? {
+                    1 => state.halted = true,
+                    2 => state.stopped = true,
+                    _ => {},
+                }
+                block.u8()?;
+                memory[0xFF00..0xFF80].copy_from_slice(block.take(0x80)?);
+                for (name, start, max) in BUFFERS {
+                    let (size, offset) = (block.u32()? as usize, block.u32()? as usize);
+                    let buffer = data
+                        .get(offset..offset + size)
+                        .ok_or_else(|| StateError::Invalid(format!("{} buffer is out of the file", name)))?;
+                    let start = start as usize;
+                    let size = size.min(max);
+                    memory[start..start + size].copy_from_slice(&buffer[..size]);
+                }
+                core_found = true;
+            },
+            b"MBC " if !len.is_multiple_of(3) => return Err(StateError::Invalid("MBC block length".to_string())),
+            b"RTC " if len != RTC_SIZE => return Err(StateError::Invalid("RTC block length".to_string())),
+            // valid MBC and RTC blocks end up here too
+            _ => import.ignored.push(name),
+        }
+    }
+    if !core_found {
+        return Err(StateError::Invalid("missing CORE block".to_string()));
+    }
+    memory.copy_within(WRAM..WRAM + ECHO.len(), ECHO.start);
+    import.state.memory = memory;
+    Ok(import)
+}
+
+pub fn save(state: &MachineState, path: &Path) -> Result<(), StateError> {
+    Ok(fs::write(path, export(state))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom() -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[TITLE][..6].copy_from_slice(b"TETRIS");
+        rom[GLOBAL_CHECKSUM].copy_from_slice(&[0x12, 0x34]);
+        rom
+    }
+
+    fn state() -> MachineState {
+        let rom = rom();
+        let mut memory = vec![0; 0x10000];
+        memory[..0x8000].copy_from_slice(&rom);
+        memory[0x8010] = 0x11;
+        memory[0xC000] = 0x22;
+        memory[0xDDFF] = 0x23;
+        memory.copy_within(WRAM..WRAM + ECHO.len(), ECHO.start);
+        memory[0xFE00] = 0x33;
+        memory[0xFF0F] = 0xE1;
+        memory[0xFF80] = 0x44;
+        memory[0xFFFF] = 0x05;
+        MachineState {
+            af: 0x01B0, bc: 0x0013, de: 0x00D8, hl: 0x014D, sp: 0xFFFE, pc: 0x0150, instruction_pc: 0x0150,
+            ime: true, ei_pending: false, halted: true, stopped: false,
+            cycles: 0, div_cycles: 0, tima_cycles: 0,
+            rom_bank: 1, rom_hash: rom_hash(&rom), rom_size: rom.len() as u32,
+            memory,
+            other_vram: Vec::new(),
+            buttons: 0,
+        }
+    }
+
+    /// Blocks of an exported file with `block` inserted before END
+    fn with_block(tag: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut data = export(&state());
+        let end = data.len() - 8 - 8;
+        let mut block = Vec::new();
+        write_block(&mut block, tag, payload);
+        data.splice(end..end, block);
+        data
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let state = state();
+        let data = export(&state);
+        assert!(is_bess(&data));
+        let imported = import(&data, &rom()).unwrap();
+        assert_eq!(imported.state, state);
+        assert_eq!(imported.state.memory[0xE000], 0x22);
+        assert_eq!(imported.state.memory[0xFDFF], 0x23);
+        assert_eq!(imported.model, "GDB");
+        assert!(imported.emulator.unwrap().starts_with("gb-emu"));
+        assert!(imported.ignored.is_empty());
+
+        // CORE comes first, or right after NAME
+        let mut tags = Vec::new();
+        let mut at = Reader { tag: "footer", data: &data[data.len() - 8..] }.u32().unwrap() as usize;
+        while at < data.len() - 8 {
+            let len = Reader { tag: "block", data: &data[at + 4..] }.u32().unwrap() as usize;
+            tags.push(&data[at..at + 4]);
+            at += 8 + len;
+        }
+        assert_eq!(tags, [b"NAME", b"CORE", b"INFO", b"END "]);
+    }
+
+    #[test]
+    fn test_blocks() {
+        let imported = import(&with_block(b"XOAM", &[0; 0x60]), &rom()).unwrap();
+        assert_eq!(imported.ignored, vec!["XOAM"]);
+        let imported = import(&with_block(b"RTC ", &[0; RTC_SIZE]), &rom()).unwrap();
+        assert_eq!(imported.ignored, vec!["RTC"]);
+        assert!(import(&with_block(b"MBC ", &[0; 4]), &rom()).is_err());
+
+        let mut other = rom();
+        other[GLOBAL_CHECKSUM.start] = 0;
+        assert!(matches!(import(&export(&state()), &other), Err(StateError::WrongRom)));
+
+        let data = export(&state());
+        assert!(matches!(import(&data[..data.len() - 20], &rom()), Err(StateError::Invalid(_))));
+    }
+}
@@ -0,0 +1,141 @@
+use std::fmt;
+
+use crate::core::memory::Memory;
+
+/// How a call frame was entered
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallKind {
+    Call,
+    Rst,
+    Interrupt,
+}
+
+impl fmt::Display for CallKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallKind::Call => write!(f, "call"),
+            CallKind::Rst => write!(f, "rst"),
+            CallKind::Interrupt => write!(f, "int"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub kind: CallKind,
+    /// Address of the calling instruction, or of the interrupted one
+    pub origin: u16,
+    pub target: u16,
+    pub return_addr: u16,
+    /// SP after the return address was pushed
+    pub sp: u16,
+}
+
+/// ## Shadow call stack
+/// Frames pushed by `CALL`, `RST` and interrupt dispatch, popped by `RET`/`RETI`.
+/// Code that drops return addresses(e.g. `POP` followed by `JP`) leaves frames
+/// behind, they are discarded once SP moves above them.
+#[derive(Clone, Debug)]
+pub struct CallStack {
+    frames: Vec<Frame>,
+    /// Value SP was last loaded with, the stack grows down from it
+    pub origin: u16,
+}
+
+impl CallStack {
+    pub fn new() -> CallStack {
+        CallStack { frames: Vec::new(), origin: 0xFFFE }
+    }
+
+    pub fn push(&mut self, frame: Frame) {
+        self.discard(frame.sp);
+        self.frames.push(frame);
+    }
+
+    /// Pops the frame returned from, `sp` is SP before the return address is popped
+    pub fn ret(&mut self, sp: u16) {
+        self.discard(sp);
+    }
+
+    /// Stack was moved, frames can't be returned to anymore
+    pub fn set_origin(&mut self, sp: u16) {
+        self.origin = sp;
+        self.frames.clear();
+    }
+
+    /// Removes frames at or below `sp`
+    fn discard(&mut self, sp: u16) {
+        while self.frames.last().is_some_and(|f| f.sp <= sp) {
+            self.frames.pop();
+        }
+    }
+
+    /// Frames from the innermost one
+    pub fn iter(&self) -> impl Iterator<Item = &Frame> {
+        self.frames.iter().rev()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+}
+
+impl Default for CallStack {
+    fn default() -> CallStack {
+        CallStack::new()
+    }
+}
+
+/// Whether `addr` follows a `CALL` or `RST` instruction, so a word on the stack
+/// with this value is likely a return address
+pub fn is_return_address(memory: &Memory, addr: u16) -> bool {
+    let before = |n: u16| memory.peek(addr.wrapping_sub(n) as usize);
+    // RST n
+    before(1) & 0xC7 == 0xC7
+        // CALL a16 / CALL cc,a16
+        || matches!(before(3), 0xCD | 0xC4 | 0xCC | 0xD4 | 0xDC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(sp: u16) -> Frame {
+        Frame { kind: CallKind::Call, origin: 0, target: 0, return_addr: 0, sp }
+    }
+
+    #[test]
+    fn test_frames() {
+        let mut stack = CallStack::new();
+        stack.push(frame(0xFFFC));
+        stack.push(frame(0xFFFA));
+        stack.push(frame(0xFFF8));
+        stack.ret(0xFFF8);
+        assert_eq!(stack.depth(), 2);
+        assert_eq!(stack.iter().next(), Some(&frame(0xFFFA)));
+
+        // return address at 0xFFFA was dropped, RET pops the outer frame
+        stack.ret(0xFFFC);
+        assert_eq!(stack.depth(), 0);
+
+        stack.push(frame(0xFFFC));
+        stack.push(frame(0xFFFA));
+        // return address was popped and another call pushed in its place
+        stack.push(frame(0xFFFA));
+        assert_eq!(stack.depth(), 2);
+
+        stack.set_origin(0xDFFF);
+        assert_eq!(stack.depth(), 0);
+    }
+
+    #[test]
+    fn test_return_address() {
+        let mut mem = Memory::new();
+        // CALL $4000; RST $38
+        mem[0xC000u16] = 0xCD;
+        mem[0xC003u16] = 0xFF;
+        assert!(is_return_address(&mem, 0xC003));
+        assert!(is_return_address(&mem, 0xC004));
+        assert!(!is_return_address(&mem, 0xC002));
+    }
+}
@@ -0,0 +1,471 @@
+use super::{opcodes::{Instruction, InstrucionTarget}, info::InstructionInfo};
+
+pub const TABLE_SIZE: usize = 0xFF + 1;
+
+/*
+    SM83 opcodes are decoded from their bit fields:
+
+        7 6 5 4 3 2 1 0
+        x x y y y z z z
+            p p q
+
+    x - opcode group
+    y - register or condition(bits 5-3), bit number for CB-prefixed opcodes
+    z - register or operation(bits 2-0)
+    p - 16bit register pair(bits 5-4)
+    q - bit 3
+
+    Register tables below are indexed by those fields.
+*/
+
+/// 8bit registers indexed by `y` or `z`
+const R: [InstrucionTarget; 8] = [
+    InstrucionTarget::B,
+    InstrucionTarget::C,
+    InstrucionTarget::D,
+    InstrucionTarget::E,
+    InstrucionTarget::H,
+    InstrucionTarget::L,
+    InstrucionTarget::HLMem,
+    InstrucionTarget::A,
+];
+
+/// 16bit register pairs indexed by `p`
+const RP: [InstrucionTarget; 4] = [
+    InstrucionTarget::BC,
+    InstrucionTarget::DE,
+    InstrucionTarget::HL,
+    InstrucionTarget::SP,
+];
+
+/// 16bit register pairs for PUSH and POP indexed by `p`
+const RP2: [InstrucionTarget; 4] = [
+    InstrucionTarget::BC,
+    InstrucionTarget::DE,
+    InstrucionTarget::HL,
+    InstrucionTarget::AF,
+];
+
+/// Jump conditions indexed by `y`
+const CC: [InstrucionTarget; 4] = [
+    InstrucionTarget::NZCond,
+    InstrucionTarget::ZCond,
+    InstrucionTarget::NCCond,
+    InstrucionTarget::CCond,
+];
+
+/// Fields of an opcode
+#[derive(Clone, Copy, Debug)]
+struct Fields {
+    x: u8,
+    y: u8,
+    z: u8,
+    p: u8,
+    q: u8,
+}
+
+impl Fields {
+    fn new(op: u8) -> Fields {
+        let y = (op >> 3) & 7;
+        Fields {
+            x: op >> 6,
+            y,
+            z: op & 7,
+            p: y >> 1,
+            q: y & 1,
+        }
+    }
+}
+
+fn is_hl_mem(target: InstrucionTarget) -> bool {
+    matches!(target, InstrucionTarget::HLMem)
+}
+
+/// ALU operation `y` between A and `source`
+fn alu(y: u8, source: InstrucionTarget) -> Instruction {
+    let a = InstrucionTarget::A;
+    match y {
+        0 => Instruction::ADD(a, source),
+        1 => Instruction::ADC(a, source),
+        2 => Instruction::SUB(a, source),
+        3 => Instruction::SBC(a, source),
+        4 => Instruction::AND(a, source),
+        5 => Instruction::XOR(a, source),
+        6 => Instruction::OR(a, source),
+        _ => Instruction::CP(a, source),
+    }
+}
+
+/// Decodes unprefixed opcode. Returns instruction and its duration in cycles,
+/// conditional instructions report duration when condition is not met.
+/// Unused opcodes are decoded as `Instruction::XXX` with 0 cycles.
+pub fn decode(op: u8) -> (Instruction, i32) {
+    let f = Fields::new(op);
+    match f.x {
+        0 => match f.z {
+            0 => match f.y {
+                0 => (Instruction::NOP, 4),
+                1 => (Instruction::LD(InstrucionTarget::A16, InstrucionTarget::SP), 20),
+                2 => (Instruction::STOP, 4),
+                3 => (Instruction::JR(InstrucionTarget::Blank, InstrucionTarget::I8), 12),
+                _ => (Instruction::JR(CC[f.y as usize - 4], InstrucionTarget::I8), 8),
+            },
+            1 => match f.q {
+                0 => (Instruction::LD(RP[f.p as usize], InstrucionTarget::U16), 12),
+                _ => (Instruction::ADD(InstrucionTarget::HL, RP[f.p as usize]), 8),
+            },
+            2 => {
+                let (a, hl) = (InstrucionTarget::A, InstrucionTarget::HLMem);
+                let instruction = match (f.q, f.p) {
+                    (0, 0) => Instruction::LD(InstrucionTarget::BCMem, a),
+                    (0, 1) => Instruction::LD(InstrucionTarget::DEMem, a),
+                    (0, 2) => Instruction::LDI(hl, a),
+                    (0, _) => Instruction::LDD(hl, a),
+                    (_, 0) => Instruction::LD(a, InstrucionTarget::BCMem),
+                    (_, 1) => Instruction::LD(a, InstrucionTarget::DEMem),
+                    (_, 2) => Instruction::LDI(a, hl),
+                    (_, _) => Instruction::LDD(a, hl),
+                };
+                (instruction, 8)
+            },
+            3 => match f.q {
+                0 => (Instruction::INC(RP[f.p as usize]), 8),
+                _ => (Instruction::DEC(RP[f.p as usize]), 8),
+            },
+            4 | 5 => {
+                let target = R[f.y as usize];
+                let cycles = if is_hl_mem(target) { 12 } else { 4 };
+                if f.z == 4 {
+                    (Instruction::INC(target), cycles)
+                } else {
+                    (Instruction::DEC(target), cycles)
+                }
+            },
+            6 => {
+                let target = R[f.y as usize];
+                let cycles = if is_hl_mem(target) { 12 } else { 8 };
+                (Instruction::LD(target, InstrucionTarget::U8), cycles)
+            },
+            _ => {
+                let instruction = match f.y {
+                    0 => Instruction::RLCA,
+                    1 => Instruction::RRCA,
+                    2 => Instruction::RLA,
+                    3 => Instruction::RRA,
+                    4 => Instruction::DAA,
+                    5 => Instruction::CPL,
+                    6 => Instruction::SCF,
+                    _ => Instruction::CCF,
+                };
+                (instruction, 4)
+            },
+        },
+        1 => {
+            let (to, from) = (R[f.y as usize], R[f.z as usize]);
+            if is_hl_mem(to) && is_hl_mem(from) {
+                return (Instruction::HALT, 4);
+            }
+            let cycles = if is_hl_mem(to) || is_hl_mem(from) { 8 } else { 4 };
+            (Instruction::LD(to, from), cycles)
+        },
+        2 => {
+            let source = R[f.z as usize];
+            let cycles = if is_hl_mem(source) { 8 } else { 4 };
+            (alu(f.y, source), cycles)
+        },
+        _ => match f.z {
+            0 => match f.y {
+                0..=3 => (Instruction::RET(CC[f.y as usize]), 8),
+                4 => (Instruction::LD(InstrucionTarget::A8, InstrucionTarget::A), 12),
+                5 => (Instruction::ADD(InstrucionTarget::SP, InstrucionTarget::I8), 16),
+                6 => (Instruction::LD(InstrucionTarget::A, InstrucionTarget::A8), 12),
+                _ => (Instruction::LD(InstrucionTarget::HL, InstrucionTarget::SPi8), 12),
+            },
+            1 => match (f.q, f.p) {
+                (0, _) => (Instruction::POP(RP2[f.p as usize]), 12),
+                (_, 0) => (Instruction::RET(InstrucionTarget::Blank), 16),
+                (_, 1) => (Instruction::RETI, 16),
+                (_, 2) => (Instruction::JP(InstrucionTarget::Blank, InstrucionTarget::HL), 4),
+                (_, _) => (Instruction::LD(InstrucionTarget::SP, InstrucionTarget::HL), 8),
+            },
+            2 => match f.y {
+                0..=3 => (Instruction::JP(CC[f.y as usize], InstrucionTarget::U16), 12),
+                4 => (Instruction::LD(InstrucionTarget::CMem, InstrucionTarget::A), 8),
+                5 => (Instruction::LD(InstrucionTarget::A16, InstrucionTarget::A), 16),
+                6 => (Instruction::LD(InstrucionTarget::A, InstrucionTarget::CMem), 8),
+                _ => (Instruction::LD(InstrucionTarget::A, InstrucionTarget::A16), 16),
+            },
+            3 => match f.y {
+                0 => (Instruction::JP(InstrucionTarget::Blank, InstrucionTarget::U16), 16),
+                1 => (Instruction::PREFIX, 4),
+                6 => (Instruction::DI, 4),
+                7 => (Instruction::EI, 4),
+                _ => (Instruction::XXX, 0),
+            },
+            4 => match f.y {
+                0..=3 => (Instruction::CALL(CC[f.y as usize], InstrucionTarget::U16), 12),
+                _ => (Instruction::XXX, 0),
+            },
+            5 => match (f.q, f.p) {
+                (0, _) => (Instruction::PUSH(RP2[f.p as usize]), 16),
+                (_, 0) => (Instruction::CALL(InstrucionTarget::Blank, InstrucionTarget::U16), 24),
+                (_, _) => (Instruction::XXX, 0),
+            },
+            6 => (alu(f.y, InstrucionTarget::U8), 8),
+            _ => (Instruction::RST(f.y as u16 * 8), 16),
+        },
+    }
+}
+
+/// Decodes CB-prefixed opcode. Returns instruction and its duration in cycles,
+/// duration includes fetching of the prefix itself.
+pub fn decode_prefixed(op: u8) -> (Instruction, i32) {
+    let f = Fields::new(op);
+    let target = R[f.z as usize];
+    let instruction = match f.x {
+        0 => match f.y {
+            0 => Instruction::RLC(target),
+            1 => Instruction::RRC(target),
+            2 => Instruction::RL(target),
+            3 => Instruction::RR(target),
+            4 => Instruction::SLA(target),
+            5 => Instruction::SRA(target),
+            6 => Instruction::SWAP(target),
+            _ => Instruction::SRL(target),
+        },
+        1 => Instruction::BIT(f.y, target),
+        2 => Instruction::RES(f.y, target),
+        _ => Instruction::SET(f.y, target),
+    };
+    let cycles = match (f.x, is_hl_mem(target)) {
+        (_, false) => 8,
+        (1, true) => 12,
+        (_, true) => 16,
+    };
+    (instruction, cycles)
+}
+
+pub fn build_table() -> [InstructionInfo; TABLE_SIZE] {
+    let mut opcode_table = [InstructionInfo::new(Instruction::XXX, 0); TABLE_SIZE];
+    for (op, entry) in opcode_table.iter_mut().enumerate() {
+        let (instruction, cycles) = decode(op as u8);
+        *entry = InstructionInfo::new(instruction, cycles);
+    }
+    opcode_table
+}
+
+pub fn build_prefix() -> [InstructionInfo; TABLE_SIZE] {
+    let mut prefix_table = [InstructionInfo::new(Instruction::XXX, 0); TABLE_SIZE];
+    for (op, entry) in prefix_table.iter_mut().enumerate() {
+        let (instruction, cycles) = decode_prefixed(op as u8);
+        *entry = InstructionInfo::prefixed(instruction, cycles);
+    }
+    prefix_table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Documented SM83 opcode map(https://www.pastraiser.com/cpu/gameboy/gameboy_opcodes.html)
+    // written in `Instruction::to_string` syntax. `OPCODE_CYCLES` of conditional instructions
+    // are given for the case when condition is not met, `OPCODE_CYCLES_TAKEN` - when it is.
+    const OPCODE_MAP: [&str; 256] = [
+        // 0x00
+        "NOP", "LD BC u16", "LD [BC] A", "INC BC", "INC B", "DEC B", "LD B u8", "RLCA",
+        "LD a16 SP", "ADD HL BC", "LD A [BC]", "DEC BC", "INC C", "DEC C", "LD C u8", "RRCA",
+        // 0x10
+        "STOP", "LD DE u16", "LD [DE] A", "INC DE", "INC D", "DEC D", "LD D u8", "RLA",
+        "JR i8", "ADD HL DE", "LD A [DE]", "DEC DE", "INC E", "DEC E", "LD E u8", "RRA",
+        // 0x20
+        "JR NZ i8", "LD HL u16", "LDI [HL] A", "INC HL", "INC H", "DEC H", "LD H u8", "DAA",
+        "JR Z i8", "ADD HL HL", "LDI A [HL]", "DEC HL", "INC L", "DEC L", "LD L u8", "CPL",
+        // 0x30
+        "JR NC i8", "LD SP u16", "LDD [HL] A", "INC SP", "INC [HL]", "DEC [HL]", "LD [HL] u8", "SCF",
+        "JR C i8", "ADD HL SP", "LDD A [HL]", "DEC SP", "INC A", "DEC A", "LD A u8", "CCF",
+        // 0x40
+        "LD B B", "LD B C", "LD B D", "LD B E", "LD B H", "LD B L", "LD B [HL]", "LD B A",
+        "LD C B", "LD C C", "LD C D", "LD C E", "LD C H", "LD C L", "LD C [HL]", "LD C A",
+        // 0x50
+        "LD D B", "LD D C", "LD D D", "LD D E", "LD D H", "LD D L", "LD D [HL]", "LD D A",
+        "LD E B", "LD E C", "LD E D", "LD E E", "LD E H", "LD E L", "LD E [HL]", "LD E A",
+        // 0x60
+        "LD H B", "LD H C", "LD H D", "LD H E", "LD H H", "LD H L", "LD H [HL]", "LD H A",
+        "LD L B", "LD L C", "LD L D", "LD L E", "LD L H", "LD L L", "LD L [HL]", "LD L A",
+        // 0x70
+        "LD [HL] B", "LD [HL] C", "LD [HL] D", "LD [HL] E", "LD [HL] H", "LD [HL] L", "HALT", "LD [HL] A",
+        "LD A B", "LD A C", "LD A D", "LD A E", "LD A H", "LD A L", "LD A [HL]", "LD A A",
+        // 0x80
+        "ADD A B", "ADD A C", "ADD A D", "ADD A E", "ADD A H", "ADD A L", "ADD A [HL]", "ADD A A",
+        "ADC A B", "ADC A C", "ADC A D", "ADC A E", "ADC A H", "ADC A L", "ADC A [HL]", "ADC A A",
+        // 0x90
+        "SUB A B", "SUB A C", "SUB A D", "SUB A E", "SUB A H", "SUB A L", "SUB A [HL]", "SUB A A",
+        "SBC A B", "SBC A C", "SBC A D", "SBC A E", "SBC A H", "SBC A L", "SBC A [HL]", "SBC A A",
+        // 0xA0
+        "AND A B", "AND A C", "AND A D", "AND A E", "AND A H", "AND A L", "AND A [HL]", "AND A A",
+        "XOR A B", "XOR A C", "XOR A D", "XOR A E", "XOR A H", "XOR A L", "XOR A [HL]", "XOR A A",
+        // 0xB0
+        "OR A B", "OR A C", "OR A D", "OR A E", "OR A H", "OR A L", "OR A [HL]", "OR A A",
+        "CP A B", "CP A C", "CP A D", "CP A E", "CP A H", "CP A L", "CP A [HL]", "CP A A",
+        // 0xC0
+        "RET NZ", "POP BC", "JP NZ u16", "JP u16", "CALL NZ u16", "PUSH BC", "ADD A u8", "RST 0x0",
+        "RET Z", "RET", "JP Z u16", "PREFIX CB", "CALL Z u16", "CALL u16", "ADC A u8", "RST 0x8",
+        // 0xD0
+        "RET NC", "POP DE", "JP NC u16", "???", "CALL NC u16", "PUSH DE", "SUB A u8", "RST 0x10",
+        "RET C", "RETI", "JP C u16", "???", "CALL C u16", "???", "SBC A u8", "RST 0x18",
+        // 0xE0
+        "LD [$FF00+u8] A", "POP HL", "LD [$FF00+C] A", "???", "???", "PUSH HL", "AND A u8", "RST 0x20",
+        "ADD SP i8", "JP HL", "LD a16 A", "???", "???", "???", "XOR A u8", "RST 0x28",
+        // 0xF0
+        "LD A [$FF00+u8]", "POP AF", "LD A [$FF00+C]", "DI", "???", "PUSH AF", "OR A u8", "RST 0x30",
+        "LD HL SP+i8", "LD SP HL", "LD A a16", "EI", "???", "???", "CP A u8", "RST 0x38",
+    ];
+
+    const OPCODE_CYCLES: [i32; 256] = [
+         4, 12,  8,  8,  4,  4,  8,  4, 20,  8,  8,  8,  4,  4,  8,  4, // 0x00
+         4, 12,  8,  8,  4,  4,  8,  4, 12,  8,  8,  8,  4,  4,  8,  4, // 0x10
+         8, 12,  8,  8,  4,  4,  8,  4,  8,  8,  8,  8,  4,  4,  8,  4, // 0x20
+         8, 12,  8,  8, 12, 12, 12,  4,  8,  8,  8,  8,  4,  4,  8,  4, // 0x30
+         4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 0x40
+         4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 0x50
+         4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 0x60
+         8,  8,  8,  8,  8,  8,  4,  8,  4,  4,  4,  4,  4,  4,  8,  4, // 0x70
+         4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 0x80
+         4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 0x90
+         4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 0xA0
+         4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 0xB0
+         8, 12, 12, 16, 12, 16,  8, 16,  8, 16, 12,  4, 12, 24,  8, 16, // 0xC0
+         8, 12, 12,  0, 12, 16,  8, 16,  8, 16, 12,  0, 12,  0,  8, 16, // 0xD0
+        12, 12,  8,  0,  0, 16,  8, 16, 16,  4, 16,  0,  0,  0,  8, 16, // 0xE0
+        12, 12,  8,  4,  0, 16,  8, 16, 12,  8, 16,  4,  0,  0,  8, 16, // 0xF0
+    ];
+
+    const OPCODE_CYCLES_TAKEN: [i32; 256] = [
+         4, 12,  8,  8,  4,  4,  8,  4, 20,  8,  8,  8,  4,  4,  8,  4, // 0x00
+         4, 12,  8,  8,  4,  4,  8,  4, 12,  8,  8,  8,  4,  4,  8,  4, // 0x10
+        12, 12,  8,  8,  4,  4,  8,  4, 12,  8,  8,  8,  4,  4,  8,  4, // 0x20
+        12, 12,  8,  8, 12, 12, 12,  4, 12,  8,  8,  8,  4,  4,  8,  4, // 0x30
+         4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 0x40
+         4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 0x50
+         4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 0x60
+         8,  8,  8,  8,  8,  8,  4,  8,  4,  4,  4,  4,  4,  4,  8,  4, // 0x70
+         4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 0x80
+         4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 0x90
+         4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 0xA0
+         4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 0xB0
+        20, 12, 16, 16, 24, 16,  8, 16, 20, 16, 16,  4, 24, 24,  8, 16, // 0xC0
+        20, 12, 16,  0, 24, 16,  8, 16, 20, 16, 16,  0, 24,  0,  8, 16, // 0xD0
+        12, 12,  8,  0,  0, 16,  8, 16, 16,  4, 16,  0,  0,  0,  8, 16, // 0xE0
+        12, 12,  8,  4,  0, 16,  8, 16, 12,  8, 16,  4,  0,  0,  8, 16, // 0xF0
+    ];
+
+    const OPCODE_LENGTHS: [u16; 256] = [
+         1,  3,  1,  1,  1,  1,  2,  1,  3,  1,  1,  1,  1,  1,  2,  1, // 0x00
+         2,  3,  1,  1,  1,  1,  2,  1,  2,  1,  1,  1,  1,  1,  2,  1, // 0x10
+         2,  3,  1,  1,  1,  1,  2,  1,  2,  1,  1,  1,  1,  1,  2,  1, // 0x20
+         2,  3,  1,  1,  1,  1,  2,  1,  2,  1,  1,  1,  1,  1,  2,  1, // 0x30
+         1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1, // 0x40
+         1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1, // 0x50
+         1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1, // 0x60
+         1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1, // 0x70
+         1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1, // 0x80
+         1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1, // 0x90
+         1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1, // 0xA0
+         1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1,  1, // 0xB0
+         1,  1,  3,  3,  3,  1,  2,  1,  1,  1,  3,  2,  3,  3,  2,  1, // 0xC0
+         1,  1,  3,  1,  3,  1,  2,  1,  1,  1,  3,  1,  3,  1,  2,  1, // 0xD0
+         2,  1,  1,  1,  1,  1,  2,  1,  2,  1,  3,  1,  1,  1,  2,  1, // 0xE0
+         2,  1,  1,  1,  1,  1,  2,  1,  2,  1,  3,  1,  1,  1,  2,  1, // 0xF0
+    ];
+
+    const PREFIX_MAP: [&str; 256] = [
+        // 0x00
+        "RLC B", "RLC C", "RLC D", "RLC E", "RLC H", "RLC L", "RLC [HL]", "RLC A",
+        "RRC B", "RRC C", "RRC D", "RRC E", "RRC H", "RRC L", "RRC [HL]", "RRC A",
+        // 0x10
+        "RL B", "RL C", "RL D", "RL E", "RL H", "RL L", "RL [HL]", "RL A",
+        "RR B", "RR C", "RR D", "RR E", "RR H", "RR L", "RR [HL]", "RR A",
+        // 0x20
+        "SLA B", "SLA C", "SLA D", "SLA E", "SLA H", "SLA L", "SLA [HL]", "SLA A",
+        "SRA B", "SRA C", "SRA D", "SRA E", "SRA H", "SRA L", "SRA [HL]", "SRA A",
+        // 0x30
+        "SWAP B", "SWAP C", "SWAP D", "SWAP E", "SWAP H", "SWAP L", "SWAP [HL]", "SWAP A",
+        "SRL B", "SRL C", "SRL D", "SRL E", "SRL H", "SRL L", "SRL [HL]", "SRL A",
+        // 0x40
+        "BIT 0 B", "BIT 0 C", "BIT 0 D", "BIT 0 E", "BIT 0 H", "BIT 0 L", "BIT 0 [HL]", "BIT 0 A",
+        "BIT 1 B", "BIT 1 C", "BIT 1 D", "BIT 1 E", "BIT 1 H", "BIT 1 L", "BIT 1 [HL]", "BIT 1 A",
+        // 0x50
+        "BIT 2 B", "BIT 2 C", "BIT 2 D", "BIT 2 E", "BIT 2 H", "BIT 2 L", "BIT 2 [HL]", "BIT 2 A",
+        "BIT 3 B", "BIT 3 C", "BIT 3 D", "BIT 3 E", "BIT 3 H", "BIT 3 L", "BIT 3 [HL]", "BIT 3 A",
+        // 0x60
+        "BIT 4 B", "BIT 4 C", "BIT 4 D", "BIT 4 E", "BIT 4 H", "BIT 4 L", "BIT 4 [HL]", "BIT 4 A",
+        "BIT 5 B", "BIT 5 C", "BIT 5 D", "BIT 5 E", "BIT 5 H", "BIT 5 L", "BIT 5 [HL]", "BIT 5 A",
+        // 0x70
+        "BIT 6 B", "BIT 6 C", "BIT 6 D", "BIT 6 E", "BIT 6 H", "BIT 6 L", "BIT 6 [HL]", "BIT 6 A",
+        "BIT 7 B", "BIT 7 C", "BIT 7 D", "BIT 7 E", "BIT 7 H", "BIT 7 L", "BIT 7 [HL]", "BIT 7 A",
+        // 0x80
+        "RES 0 B", "RES 0 C", "RES 0 D", "RES 0 E", "RES 0 H", "RES 0 L", "RES 0 [HL]", "RES 0 A",
+        "RES 1 B", "RES 1 C", "RES 1 D", "RES 1 E", "RES 1 H", "RES 1 L", "RES 1 [HL]", "RES 1 A",
+        // 0x90
+        "RES 2 B", "RES 2 C", "RES 2 D", "RES 2 E", "RES 2 H", "RES 2 L", "RES 2 [HL]", "RES 2 A",
+        "RES 3 B", "RES 3 C", "RES 3 D", "RES 3 E", "RES 3 H", "RES 3 L", "RES 3 [HL]", "RES 3 A",
+        // 0xA0
+        "RES 4 B", "RES 4 C", "RES 4 D", "RES 4 E", "RES 4 H", "RES 4 L", "RES 4 [HL]", "RES 4 A",
+        "RES 5 B", "RES 5 C", "RES 5 D", "RES 5 E", "RES 5 H", "RES 5 L", "RES 5 [HL]", "RES 5 A",
+        // 0xB0
+        "RES 6 B", "RES 6 C", "RES 6 D", "RES 6 E", "RES 6 H", "RES 6 L", "RES 6 [HL]", "RES 6 A",
+        "RES 7 B", "RES 7 C", "RES 7 D", "RES 7 E", "RES 7 H", "RES 7 L", "RES 7 [HL]", "RES 7 A",
+        // 0xC0
+        "SET 0 B", "SET 0 C", "SET 0 D", "SET 0 E", "SET 0 H", "SET 0 L", "SET 0 [HL]", "SET 0 A",
+        "SET 1 B", "SET 1 C", "SET 1 D", "SET 1 E", "SET 1 H", "SET 1 L", "SET 1 [HL]", "SET 1 A",
+        // 0xD0
+        "SET 2 B", "SET 2 C", "SET 2 D", "SET 2 E", "SET 2 H", "SET 2 L", "SET 2 [HL]", "SET 2 A",
+        "SET 3 B", "SET 3 C", "SET 3 D", "SET 3 E", "SET 3 H", "SET 3 L", "SET 3 [HL]", "SET 3 A",
+        // 0xE0
+        "SET 4 B", "SET 4 C", "SET 4 D", "SET 4 E", "SET 4 H", "SET 4 L", "SET 4 [HL]", "SET 4 A",
+        "SET 5 B", "SET 5 C", "SET 5 D", "SET 5 E", "SET 5 H", "SET 5 L", "SET 5 [HL]", "SET 5 A",
+        // 0xF0
+        "SET 6 B", "SET 6 C", "SET 6 D", "SET 6 E", "SET 6 H", "SET 6 L", "SET 6 [HL]", "SET 6 A",
+        "SET 7 B", "SET 7 C", "SET 7 D", "SET 7 E", "SET 7 H", "SET 7 L", "SET 7 [HL]", "SET 7 A",
+    ];
+
+    const PREFIX_CYCLES: [i32; 256] = [
+         8,  8,  8,  8,  8,  8, 16,  8,  8,  8,  8,  8,  8,  8, 16,  8, // 0x00
+         8,  8,  8,  8,  8,  8, 16,  8,  8,  8,  8,  8,  8,  8, 16,  8, // 0x10
+         8,  8,  8,  8,  8,  8, 16,  8,  8,  8,  8,  8,  8,  8, 16,  8, // 0x20
+         8,  8,  8,  8,  8,  8, 16,  8,  8,  8,  8,  8,  8,  8, 16,  8, // 0x30
+         8,  8,  8,  8,  8,  8, 12,  8,  8,  8,  8,  8,  8,  8, 12,  8, // 0x40
+         8,  8,  8,  8,  8,  8, 12,  8,  8,  8,  8,  8,  8,  8, 12,  8, // 0x50
+         8,  8,  8,  8,  8,  8, 12,  8,  8,  8,  8,  8,  8,  8, 12,  8, // 0x60
+         8,  8,  8,  8,  8,  8, 12,  8,  8,  8,  8,  8,  8,  8, 12,  8, // 0x70
+         8,  8,  8,  8,  8,  8, 16,  8,  8,  8,  8,  8,  8,  8, 16,  8, // 0x80
+         8,  8,  8,  8,  8,  8, 16,  8,  8,  8,  8,  8,  8,  8, 16,  8, // 0x90
+         8,  8,  8,  8,  8,  8, 16,  8,  8,  8,  8,  8,  8,  8, 16,  8, // 0xA0
+         8,  8,  8,  8,  8,  8, 16,  8,  8,  8,  8,  8,  8,  8, 16,  8, // 0xB0
+         8,  8,  8,  8,  8,  8, 16,  8,  8,  8,  8,  8,  8,  8, 16,  8, // 0xC0
+         8,  8,  8,  8,  8,  8, 16,  8,  8,  8,  8,  8,  8,  8, 16,  8, // 0xD0
+         8,  8,  8,  8,  8,  8, 16,  8,  8,  8,  8,  8,  8,  8, 16,  8, // 0xE0
+         8,  8,  8,  8,  8,  8, 16,  8,  8,  8,  8,  8,  8,  8, 16,  8, // 0xF0
+    ];
+
+    #[test]
+    fn test_opcode_map() {
+        let table = build_table();
+        for (op, info) in table.iter().enumerate() {
+            assert_eq!(info.instruction.to_string(), OPCODE_MAP[op], "mnemonic of opcode {:#04X}", op);
+            assert_eq!(info.cycles, OPCODE_CYCLES[op], "cycles of opcode {:#04X}", op);
+            assert_eq!(info.cycles_taken, OPCODE_CYCLES_TAKEN[op], "taken cycles of opcode {:#04X}", op);
+            assert_eq!(info.length, OPCODE_LENGTHS[op], "length of opcode {:#04X}", op);
+        }
+    }
+
+    #[test]
+    fn test_prefix_map() {
+        let table = build_prefix();
+        for (op, info) in table.iter().enumerate() {
+            assert_eq!(info.instruction.to_string(), PREFIX_MAP[op], "mnemonic of opcode CB {:#04X}", op);
+            assert_eq!(info.cycles, PREFIX_CYCLES[op], "cycles of opcode CB {:#04X}", op);
+            assert_eq!(info.length, 2, "length of opcode CB {:#04X}", op);
+        }
+    }
+}
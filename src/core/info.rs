@@ -0,0 +1,236 @@
+use std::fmt;
+
+use super::opcodes::{Instruction, InstrucionTarget};
+
+/// Set of CPU flags, bits are laid out the same way as in F register
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Flags(pub u8);
+
+impl Flags {
+    pub const NONE: Flags = Flags(0x00);
+    pub const Z: Flags = Flags(0x80);
+    pub const N: Flags = Flags(0x40);
+    pub const H: Flags = Flags(0x20);
+    pub const C: Flags = Flags(0x10);
+    pub const ALL: Flags = Flags(0xF0);
+
+    pub fn contains(self, other: Flags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn union(self, other: Flags) -> Flags {
+        Flags(self.0 | other.0)
+    }
+}
+
+/// Formats flags as `ZNHC` with `-` in place of missing ones
+impl fmt::Display for Flags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (flag, name) in [(Flags::Z, 'Z'), (Flags::N, 'N'), (Flags::H, 'H'), (Flags::C, 'C')] {
+            write!(f, "{}", if self.contains(flag) { name } else { '-' })?;
+        }
+        Ok(())
+    }
+}
+
+/// ## Instruction metadata
+/// Entry of opcode tables. Everything besides `instruction` and `cycles` is derived
+/// from the instruction itself.
+#[derive(Clone, Copy, Debug)]
+pub struct InstructionInfo {
+    pub instruction: Instruction,
+
+    /// Size in bytes including opcode(and `0xCB` prefix)
+    pub length: u16,
+
+    /// Duration in cycles, for conditional instructions - when condition is not met
+    pub cycles: i32,
+
+    /// Duration in cycles when condition is met, same as `cycles` for the rest
+    pub cycles_taken: i32,
+
+    /// Flags the result depends on
+    pub flags_read: Flags,
+
+    /// Flags modified by the instruction(including reset and set ones)
+    pub flags_written: Flags,
+}
+
+impl InstructionInfo {
+    /// Builds metadata of unprefixed instruction
+    pub fn new(instruction: Instruction, cycles: i32) -> InstructionInfo {
+        InstructionInfo {
+            instruction,
+            length: length(instruction),
+            cycles,
+            cycles_taken: cycles + taken_penalty(instruction),
+            flags_read: flags_read(instruction),
+            flags_written: flags_written(instruction),
+        }
+    }
+
+    /// Builds metadata of CB-prefixed instruction, `cycles` include the prefix
+    pub fn prefixed(instruction: Instruction, cycles: i32) -> InstructionInfo {
+        InstructionInfo {
+            length: 2,
+            ..InstructionInfo::new(instruction, cycles)
+        }
+    }
+
+    /// Returns true if duration depends on jump condition
+    pub fn is_conditional(&self) -> bool {
+        self.cycles != self.cycles_taken
+    }
+}
+
+/// Size of immediate operand in bytes
+fn operand_length(target: InstrucionTarget) -> u16 {
+    match target {
+        InstrucionTarget::U8 |
+        InstrucionTarget::A8 |
+        InstrucionTarget::I8 |
+        InstrucionTarget::SPi8 => 1,
+        InstrucionTarget::U16 |
+        InstrucionTarget::A16 => 2,
+        _ => 0,
+    }
+}
+
+fn length(instruction: Instruction) -> u16 {
+    let operands = match instruction {
+        Instruction::LD(to, from) |
+        Instruction::LDI(to, from) |
+        Instruction::LDD(to, from) |
+        Instruction::ADD(to, from) |
+        Instruction::ADC(to, from) |
+        Instruction::SUB(to, from) |
+        Instruction::SBC(to, from) |
+        Instruction::AND(to, from) |
+        Instruction::XOR(to, from) |
+        Instruction::OR(to, from) |
+        Instruction::CP(to, from) |
+        Instruction::JP(to, from) |
+        Instruction::JR(to, from) |
+        Instruction::CALL(to, from) => operand_length(to) + operand_length(from),
+        // STOP is followed by a byte that is skipped
+        Instruction::STOP |
+        Instruction::PREFIX => 1,
+        _ => 0,
+    };
+    1 + operands
+}
+
+/// Extra cycles spent when jump condition is met
+fn taken_penalty(instruction: Instruction) -> i32 {
+    match instruction {
+        Instruction::JR(InstrucionTarget::Blank, _) |
+        Instruction::JP(InstrucionTarget::Blank, _) |
+        Instruction::CALL(InstrucionTarget::Blank, _) |
+        Instruction::RET(InstrucionTarget::Blank) => 0,
+        Instruction::JR(_, _) | Instruction::JP(_, _) => 4,
+        Instruction::CALL(_, _) | Instruction::RET(_) => 12,
+        _ => 0,
+    }
+}
+
+/// Flag checked by jump condition
+fn condition_flag(cond: InstrucionTarget) -> Flags {
+    match cond {
+        InstrucionTarget::ZCond | InstrucionTarget::NZCond => Flags::Z,
+        InstrucionTarget::CCond | InstrucionTarget::NCCond => Flags::C,
+        _ => Flags::NONE,
+    }
+}
+
+fn flags_read(instruction: Instruction) -> Flags {
+    match instruction {
+        Instruction::ADC(_, _) |
+        Instruction::SBC(_, _) |
+        Instruction::RLA |
+        Instruction::RRA |
+        Instruction::RL(_) |
+        Instruction::RR(_) |
+        Instruction::CCF => Flags::C,
+        Instruction::DAA => Flags::N.union(Flags::H).union(Flags::C),
+        Instruction::PUSH(InstrucionTarget::AF) => Flags::ALL,
+        Instruction::JP(cond, _) |
+        Instruction::JR(cond, _) |
+        Instruction::CALL(cond, _) |
+        Instruction::RET(cond) => condition_flag(cond),
+        _ => Flags::NONE,
+    }
+}
+
+fn flags_written(instruction: Instruction) -> Flags {
+    let znh = Flags::Z.union(Flags::N).union(Flags::H);
+    let nhc = Flags::N.union(Flags::H).union(Flags::C);
+    match instruction {
+        Instruction::LD(InstrucionTarget::HL, InstrucionTarget::SPi8) |
+        Instruction::POP(InstrucionTarget::AF) |
+        Instruction::ADD(InstrucionTarget::SP, _) => Flags::ALL,
+        Instruction::ADD(InstrucionTarget::HL, _) => nhc,
+        Instruction::ADD(_, _) |
+        Instruction::ADC(_, _) |
+        Instruction::SUB(_, _) |
+        Instruction::SBC(_, _) |
+        Instruction::AND(_, _) |
+        Instruction::XOR(_, _) |
+        Instruction::OR(_, _) |
+        Instruction::CP(_, _) |
+        Instruction::RLCA |
+        Instruction::RLA |
+        Instruction::RRCA |
+        Instruction::RRA |
+        Instruction::RLC(_) |
+        Instruction::RL(_) |
+        Instruction::RRC(_) |
+        Instruction::RR(_) |
+        Instruction::SLA(_) |
+        Instruction::SWAP(_) |
+        Instruction::SRA(_) |
+        Instruction::SRL(_) => Flags::ALL,
+        Instruction::INC(op) | Instruction::DEC(op) => match op {
+            InstrucionTarget::BC |
+            InstrucionTarget::DE |
+            InstrucionTarget::HL |
+            InstrucionTarget::SP => Flags::NONE,
+            _ => znh,
+        },
+        Instruction::DAA => Flags::Z.union(Flags::H).union(Flags::C),
+        Instruction::CPL => Flags::N.union(Flags::H),
+        Instruction::SCF | Instruction::CCF => nhc,
+        Instruction::BIT(_, _) => znh,
+        _ => Flags::NONE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flags_display() {
+        assert_eq!(Flags::ALL.to_string(), "ZNHC");
+        assert_eq!(Flags::Z.union(Flags::C).to_string(), "Z--C");
+        assert_eq!(Flags::NONE.to_string(), "----");
+    }
+
+    #[test]
+    fn test_instruction_info() {
+        let jr = InstructionInfo::new(Instruction::JR(InstrucionTarget::NZCond, InstrucionTarget::I8), 8);
+        assert_eq!(jr.length, 2);
+        assert_eq!(jr.cycles_taken, 12);
+        assert!(jr.is_conditional());
+        assert_eq!(jr.flags_read, Flags::Z);
+        assert_eq!(jr.flags_written, Flags::NONE);
+
+        let inc = InstructionInfo::new(Instruction::INC(InstrucionTarget::HLMem), 12);
+        assert_eq!(inc.length, 1);
+        assert!(!inc.is_conditional());
+        assert_eq!(inc.flags_written.to_string(), "ZNH-");
+
+        let bit = InstructionInfo::prefixed(Instruction::BIT(7, InstrucionTarget::H), 8);
+        assert_eq!(bit.length, 2);
+        assert_eq!(bit.flags_written.to_string(), "ZNH-");
+    }
+}
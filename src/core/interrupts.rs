@@ -0,0 +1,153 @@
+use crate::core::memory::Memory;
+
+/// Interrupt sources in priority order(VBlank has the highest priority)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interrupt {
+    VBlank,
+    LcdStat,
+    Timer,
+    Serial,
+    Joypad,
+}
+
+impl Interrupt {
+    pub const ALL: [Interrupt; 5] = [
+        Interrupt::VBlank,
+        Interrupt::LcdStat,
+        Interrupt::Timer,
+        Interrupt::Serial,
+        Interrupt::Joypad,
+    ];
+
+    /// Bit of the interrupt in IE and IF registers
+    pub fn bit(self) -> i32 {
+        match self {
+            Interrupt::VBlank => 0,
+            Interrupt::LcdStat => 1,
+            Interrupt::Timer => 2,
+            Interrupt::Serial => 3,
+            Interrupt::Joypad => 4,
+        }
+    }
+
+    /// Address of the interrupt handler
+    pub fn vector(self) -> u16 {
+        0x0040 + 8 * self.bit() as u16
+    }
+
+    /// Returns the highest priority interrupt among set bits of `flags`
+    pub fn highest(flags: u8) -> Option<Interrupt> {
+        Interrupt::ALL
+            .iter()
+            .copied()
+            .find(|i| (flags >> i.bit()) & 1 == 1)
+    }
+}
+
+/// ## Interrupt controller
+/// Owns Interrupt Master Enable flag and `EI` delay. IE and IF registers themselves
+/// live in `Memory` since they are memory mapped. \
+/// Rules followed:
+/// - only the highest priority pending interrupt is dispatched
+/// - IME is cleared on dispatch
+/// - `EI` enables interrupts after the instruction following it, `DI` and `RETI` act immediately
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InterruptController {
+    /// Interrupt Master Enable
+    pub ime: bool,
+
+    /// `EI` was executed, IME is set once the next instruction is done
+    ei_pending: bool,
+}
+
+impl InterruptController {
+    pub fn new() -> InterruptController {
+        InterruptController::default()
+    }
+
+    /// Interrupts that are both requested(IF) and enabled(IE)
+    pub fn pending(&self, memory: &Memory) -> u8 {
+        memory.ie() & memory.iflag() & 0x1F
+    }
+
+    /// Highest priority interrupt that is requested and enabled, regardless of IME
+    pub fn highest_pending(&self, memory: &Memory) -> Option<Interrupt> {
+        Interrupt::highest(self.pending(memory))
+    }
+
+    /// Returns true if an interrupt should be dispatched before the next instruction
+    pub fn should_dispatch(&self, memory: &Memory) -> bool {
+        self.ime && self.pending(memory) != 0
+    }
+
+    /// Sets IF bit of `interrupt`
+    pub fn request(&self, memory: &mut Memory, interrupt: Interrupt) {
+        memory.set_iflag_bit(interrupt.bit());
+    }
+
+    /// `EI` - IME is set after the next instruction
+    pub fn enable_delayed(&mut self) {
+        self.ei_pending = true;
+    }
+
+    /// `RETI` - IME is set immediately
+    pub fn enable(&mut self) {
+        self.ime = true;
+        self.ei_pending = false;
+    }
+
+    /// `DI` and interrupt dispatch - IME is cleared, pending `EI` is cancelled
+    pub fn disable(&mut self) {
+        self.ime = false;
+        self.ei_pending = false;
+    }
+
+    /// Returns true if `EI` is waiting for the current instruction to finish
+    pub fn is_ei_pending(&self) -> bool {
+        self.ei_pending
+    }
+
+    /// Called once the instruction following `EI` is executed
+    pub fn commit_ei(&mut self) {
+        self.enable();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interrupt_priority() {
+        assert_eq!(Interrupt::highest(0b11111), Some(Interrupt::VBlank));
+        assert_eq!(Interrupt::highest(0b10100), Some(Interrupt::Timer));
+        assert_eq!(Interrupt::highest(0b10000), Some(Interrupt::Joypad));
+        assert_eq!(Interrupt::highest(0b00000), None);
+        assert_eq!(Interrupt::Serial.vector(), 0x0058);
+    }
+
+    #[test]
+    fn test_pending_ignores_upper_bits() {
+        let mut mem = Memory::new();
+        let ic = InterruptController::new();
+        mem[0xFFFFu16] = 0xE0;
+        mem[0xFF0Fu16] = 0xE0;
+        assert_eq!(ic.pending(&mem), 0);
+        assert_eq!(ic.highest_pending(&mem), None);
+
+        ic.request(&mut mem, Interrupt::Timer);
+        mem[0xFFFFu16] = 0x04;
+        assert_eq!(ic.highest_pending(&mem), Some(Interrupt::Timer));
+        assert!(!ic.should_dispatch(&mem));
+    }
+
+    #[test]
+    fn test_di_cancels_ei() {
+        let mut ic = InterruptController::new();
+        ic.enable_delayed();
+        assert!(!ic.ime);
+        ic.disable();
+        assert!(!ic.is_ei_pending());
+        assert!(!ic.ime);
+    }
+}
@@ -1,55 +1,214 @@
-use std::{ops::{Index, IndexMut}, path::Path, fs::File, io::Read};
-use crate::core::register::Register;
+use std::{ops::{Index, IndexMut}, path::Path, fs, io};
+use crate::core::{register::Register, watchpoints::Watchpoints};
 
-const ROM_SIZE: u64 = 32 * 1024;
+/// Size of cartridge ROM mapped to 0x0000-0x7FFF
+const ROM_SIZE: usize = 32 * 1024;
+
+/// Size of a single ROM bank
+pub const ROM_BANK_SIZE: usize = 16 * 1024;
+
+/// Size of the DMG boot ROM
+pub const BOOT_ROM_SIZE: usize = 0x100;
+
+/// Writing non-zero here unmaps the boot ROM
+const BOOT_OFF: usize = 0xFF50;
+
+/// VRAM area, CGB has two banks of it
+const VRAM: std::ops::Range<usize> = 0x8000..0xA000;
+
+/// CGB VRAM bank select, bit 0
+const VBK: usize = 0xFF4F;
+
+/// Cartridge header flag marking CGB support in bit 7
+const CGB_FLAG: usize = 0x0143;
+
+/// Joypad register, bits 4 and 5 select the direction and action rows,
+/// pressed buttons of selected rows read as 0 in the low bits
+const P1: usize = 0xFF00;
+
+/// Joypad buttons by their bit in `Memory::set_buttons`
+pub const BUTTONS: [&str; 8] = ["a", "b", "select", "start", "right", "left", "up", "down"];
 
 pub struct Memory {
     _mem: [u8; 64*1024],
 
+    /// Whole cartridge image, including banks that are not mapped
+    rom: Vec<u8>,
+    /// Mapped over the start of the cartridge until disabled by a write to 0xFF50,
+    /// empty when booting is skipped
+    boot_rom: Vec<u8>,
+    /// CGB VRAM bank that isn't mapped at 0x8000, the mapped one lives in the
+    /// address space like the rest of memory
+    other_vram: Vec<u8>,
+    /// Held joypad buttons, bits as in `BUTTONS`
+    buttons: u8,
+
     div: usize,
     tima: usize,
     tma: usize,
     tac: usize,
 
     ie: usize,
-    iflag: usize
+    iflag: usize,
+
+    /// Checked on every `read` and `write`, raw indexing bypasses them
+    pub watchpoints: Watchpoints,
 }
 
 impl Memory {
     pub fn new() -> Memory {
         Memory{
             _mem: [0; 64*1024],
+            rom: Vec::new(),
+            boot_rom: Vec::new(),
+            other_vram: vec![0; VRAM.len()],
+            buttons: 0,
             // timers
             div: 0xFF04,
             tima: 0xFF05,
             tma: 0xFF06,
-            tac: 0xFF06,
+            tac: 0xFF07,
 
             // interrupts
             ie: 0xFFFF,
             iflag: 0xFF0F,
+
+            watchpoints: Watchpoints::new(),
+        }
+    }
+
+    pub fn load(&mut self, path: &Path) -> io::Result<()> {
+        let data = fs::read(path)?;
+        if data.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid ROM size"));
         }
+        self.load_rom(data);
+        Ok(())
     }
 
-    pub fn load(&mut self, path: &Path){
-        let file = File::open(path).unwrap();
-        let mut data = Vec::new();
+    /// Replaces the cartridge and maps it in
+    pub fn load_rom(&mut self, data: Vec<u8>) {
+        self.rom = data;
+        self.map_rom();
+    }
 
-        let length = file.take(ROM_SIZE).read_to_end(&mut data).unwrap();
-        if length <= ROM_SIZE as usize {
-            for (place, value) in self._mem.iter_mut().zip(data.iter()) {
-                *place = *value
-            }
+    /// Loads boot ROM and maps it in, the CPU has to be reset to run it
+    pub fn load_boot_rom(&mut self, path: &Path) -> io::Result<()> {
+        let data = fs::read(path)?;
+        if data.len() != BOOT_ROM_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid boot ROM size"));
+        }
+        self.boot_rom = data;
+        self.map_rom();
+        Ok(())
+    }
+
+    /// Whether the cartridge supports CGB, which enables VRAM banking
+    pub fn is_cgb(&self) -> bool {
+        self.rom.get(CGB_FLAG).is_some_and(|flag| flag & 0x80 != 0)
+    }
+
+    /// VRAM bank mapped at 0x8000, always 0 on DMG
+    pub fn vram_bank(&self) -> u8 {
+        if self.is_cgb() { self._mem[VBK] & 1 } else { 0 }
+    }
+
+    /// Byte at `addr` in VRAM bank `bank`, without side effects
+    pub fn peek_vram(&self, bank: u8, addr: u16) -> u8 {
+        let addr = addr as usize;
+        if bank == self.vram_bank() {
+            self._mem[addr]
         } else {
-            panic!("Invalid ROM size")
+            self.other_vram[addr - VRAM.start]
         }
     }
 
-    pub fn read(&self, i: usize) -> u8 {
+    /// VRAM bank that isn't mapped, saved with states
+    pub fn other_vram(&self) -> &[u8] {
+        &self.other_vram
+    }
+
+    /// Replaces the unmapped VRAM bank, an empty `data` clears it
+    pub fn restore_other_vram(&mut self, data: &[u8]) {
+        self.other_vram.fill(0);
+        for (place, value) in self.other_vram.iter_mut().zip(data) {
+            *place = *value;
+        }
+    }
+
+    /// Whether the boot ROM covers the start of the cartridge
+    pub fn boot_rom_mapped(&self) -> bool {
+        !self.boot_rom.is_empty() && self._mem[BOOT_OFF] == 0
+    }
+
+    fn map_rom(&mut self) {
+        for (place, value) in self._mem[..ROM_SIZE].iter_mut().zip(self.rom.iter()) {
+            *place = *value
+        }
+        if self.boot_rom_mapped() {
+            self._mem[..BOOT_ROM_SIZE].copy_from_slice(&self.boot_rom);
+        }
+    }
+
+    /// Replaces the whole address space with `data`, the cartridge and watchpoints are kept
+    pub fn restore(&mut self, data: &[u8]) {
+        self._mem.copy_from_slice(data);
+    }
+
+    /// Clears RAM and I/O to the power up state, the cartridge, boot ROM and watchpoints are kept.
+    /// Undoes writes done to the ROM area as well
+    pub fn reset(&mut self) {
+        self._mem.fill(0);
+        self.other_vram.fill(0);
+        self.buttons = 0;
+        self.map_rom();
+        if self.boot_rom_mapped() {
+            self.power_on_registers();
+        } else {
+            self.reset_hardware_registers();
+        }
+    }
+
+    /// Cartridge image as it was loaded
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    /// ROM bank mapped to 0x4000-0x7FFF. \
+    /// There is no mapper support yet, so it's always bank 1
+    pub fn rom_bank(&self) -> u16 {
+        1
+    }
+
+    /// Read done by CPU, triggers watchpoints
+    pub fn read(&mut self, i: usize) -> u8 {
+        let v = self._mem[i];
+        if !self.watchpoints.is_empty() {
+            self.watchpoints.on_read(i as u16, v);
+        }
+        v
+    }
+
+    /// Read without side effects, for debugger and disassembler
+    pub fn peek(&self, i: usize) -> u8 {
         self._mem[i]
     }
 
+    /// Whole address space, read without side effects
+    pub fn peek_all(&self) -> &[u8] {
+        &self._mem
+    }
+
+    /// Write done by CPU, triggers watchpoints even if the address is read-only
     pub fn write(&mut self, i: usize, v: u8) {
+        let old = self._mem[i];
+        self.store(i, v);
+        if !self.watchpoints.is_empty() {
+            self.watchpoints.on_write(i as u16, old, self._mem[i]);
+        }
+    }
+
+    fn store(&mut self, i: usize, v: u8) {
         if i < 0x0800 { return; }
         if (0xFEA0..=0xFEFFusize).contains(&i) { return; }
         if (0xE000..=0xFDFFusize).contains(&i) { return; }
@@ -62,6 +221,25 @@ impl Memory {
             self._mem[i] = 0;
         }
 
+        if i == VBK && self.is_cgb() {
+            if (v ^ self._mem[VBK]) & 1 != 0 {
+                self._mem[VRAM].swap_with_slice(&mut self.other_vram);
+            }
+            self._mem[i] = 0xFE | v;
+            return;
+        }
+
+        if i == BOOT_OFF && self.boot_rom_mapped() {
+            self._mem[i] = v;
+            self.map_rom();
+        }
+
+        if i == P1 {
+            self._mem[i] = v & 0x30;
+            self.update_p1();
+            return;
+        }
+
         self._mem[i] = v;
     }
 
@@ -73,6 +251,56 @@ impl Memory {
 
         self._mem[self.iflag] = 0xE1;
         self._mem[self.ie] = 0x00;
+
+        self._mem[P1] = 0x30;
+        self.update_p1();
+    }
+
+    /// I/O registers at power on, the boot ROM sets up the rest.
+    /// Unused bits of TAC and IF read as 1
+    fn power_on_registers(&mut self) {
+        self._mem[self.div]  = 0x00;
+        self._mem[self.tima] = 0x00;
+        self._mem[self.tma]  = 0x00;
+        self._mem[self.tac]  = 0xF8;
+
+        self._mem[self.iflag] = 0xE0;
+        self._mem[self.ie] = 0x00;
+
+        self._mem[P1] = 0x30;
+        self.update_p1();
+    }
+
+    // Joypad
+
+    pub fn buttons(&self) -> u8 { self.buttons }
+
+    /// Replaces held buttons, requests joypad interrupt when a button of a selected
+    /// row gets pressed
+    pub fn set_buttons(&mut self, buttons: u8) {
+        let released = self._mem[P1] & 0x0F;
+        self.buttons = buttons;
+        self.update_p1();
+        if released & !self._mem[P1] & 0x0F != 0 {
+            self.set_iflag_bit(4);
+        }
+    }
+
+    /// Restores held buttons saved with the rest of memory, P1 already reflects them
+    pub fn restore_buttons(&mut self, buttons: u8) {
+        self.buttons = buttons;
+    }
+
+    fn update_p1(&mut self) {
+        let select = self._mem[P1] & 0x30;
+        let mut pressed = 0;
+        if select & 0x10 == 0 {
+            pressed |= self.buttons >> 4;
+        }
+        if select & 0x20 == 0 {
+            pressed |= self.buttons & 0x0F;
+        }
+        self._mem[P1] = 0xC0 | select | (!pressed & 0x0F);
     }
 
     // Timers
@@ -102,17 +330,12 @@ impl Memory {
     // Interrupts
 
     /// Interrupt Enable
-    pub fn is_ie_set(&mut self, id: i32) -> bool { 
-        let r = self._mem[self.ie];
-        (r >> id) & 1 == 1 
-    }
+    pub fn ie(&self) -> u8 { self._mem[self.ie] }
 
     /// Interrupt Flag
+    pub fn iflag(&self) -> u8 { self._mem[self.iflag] }
+    pub fn set_iflag_bit(&mut self, id: i32) { self._mem[self.iflag] |= 1 << id; }
     pub fn reset_iflag_bit(&mut self, id: i32) { self._mem[self.iflag] &= !(1 << id); }
-    pub fn is_iflag_set(&mut self, id: i32) -> bool { 
-        let r = self._mem[self.iflag];
-        (r >> id) & 1 == 1 
-    }
 }
 
 impl Index<usize> for Memory {
@@ -161,6 +384,110 @@ impl IndexMut<Register> for Memory {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_reset() {
+        let mut mem = Memory::new();
+        mem.load_rom(vec![0x31, 0xFE, 0xFF]);
+        mem[0x0001u16] = 0x00;
+        mem[0xC000u16] = 0x42;
+        mem[0xFF05u16] = 0x10;
+        mem.reset();
+        assert_eq!(&mem.peek_all()[..4], &[0x31, 0xFE, 0xFF, 0x00]);
+        assert_eq!(mem.peek(0xC000), 0x00);
+        assert_eq!(mem.tima(), 0x00);
+        assert_eq!(mem.div(), 0xAB);
+        assert_eq!(mem.rom().len(), 3);
+    }
+
+    #[test]
+    fn test_boot_rom() {
+        let path = std::env::temp_dir().join(format!("gb-emu-boot-{}.bin", std::process::id()));
+        fs::write(&path, [0x31; BOOT_ROM_SIZE]).unwrap();
+        let mut mem = Memory::new();
+        mem.load_rom(vec![0x00; 0x200]);
+        mem.load_boot_rom(&path).unwrap();
+        assert!(mem.boot_rom_mapped());
+        assert_eq!(mem.peek(0x00FF), 0x31);
+        assert_eq!(mem.peek(0x0100), 0x00);
+
+        mem.write(BOOT_OFF, 0x01);
+        assert!(!mem.boot_rom_mapped());
+        assert_eq!(mem.peek(0x00FF), 0x00);
+        mem.reset();
+        assert_eq!(mem.peek(0x00FF), 0x31);
+        // power-on values, no VBlank pending and DIV not advanced by the boot ROM yet
+        assert_eq!(mem.peek(0xFF04), 0x00);
+        assert_eq!(mem.peek(0xFF0F), 0xE0);
+
+        fs::write(&path, [0x31; 0x10]).unwrap();
+        assert!(Memory::new().load_boot_rom(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_vram_banks() {
+        let mut mem = Memory::new();
+        let mut rom = vec![0; 0x8000];
+        rom[CGB_FLAG] = 0x80;
+        mem.load_rom(rom);
+        assert!(mem.is_cgb());
+        mem.write(0x8000, 0x11);
+        mem.write(VBK, 0x01);
+        assert_eq!(mem.vram_bank(), 1);
+        assert_eq!(mem.peek(0x8000), 0x00);
+        mem.write(0x8000, 0x22);
+        assert_eq!(mem.peek_vram(0, 0x8000), 0x11);
+        assert_eq!(mem.peek_vram(1, 0x8000), 0x22);
+        mem.write(VBK, 0x00);
+        assert_eq!(mem.peek(0x8000), 0x11);
+        assert_eq!(mem.peek(VBK), 0xFE);
+
+        // DMG has a single bank
+        let mut dmg = Memory::new();
+        dmg.load_rom(vec![0; 0x8000]);
+        dmg.write(VBK, 0x01);
+        assert_eq!(dmg.vram_bank(), 0);
+    }
+
+    #[test]
+    fn test_joypad() {
+        let mut mem = Memory::new();
+        mem.reset();
+        mem[0xFF0Fu16] = 0x00;
+        assert_eq!(mem.peek(P1), 0xFF);
+        // start and down held, rows aren't selected
+        mem.set_buttons(0x88);
+        assert_eq!(mem.peek(P1), 0xFF);
+        assert_eq!(mem.iflag(), 0x00);
+        mem.write(P1, 0x20);
+        assert_eq!(mem.peek(P1), 0xE7);
+        mem.write(P1, 0x10);
+        assert_eq!(mem.peek(P1), 0xD7);
+        mem.set_buttons(0x89);
+        assert_eq!(mem.peek(P1), 0xD6);
+        assert_eq!(mem.iflag(), 0x10);
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        use crate::core::watchpoints::{Watchpoint, WatchKind, WatchHit};
+
+        let mut mem = Memory::new();
+        let read = Watchpoint { range: 0xC000..=0xC000, kind: WatchKind::Read };
+        mem.watchpoints.toggle(read.clone());
+        mem[0xC000u16] = 0x42;
+        assert_eq!(mem.peek(0xC000), 0x42);
+        assert_eq!(mem.watchpoints.take_hits(), vec![]);
+        assert_eq!(mem.read(0xC000), 0x42);
+        assert_eq!(mem.watchpoints.take_hits(), vec![WatchHit { addr: 0xC000, watchpoint: read, old: 0x42, new: 0x42 }]);
+
+        // writes to ROM are ignored, but still seen
+        let write = Watchpoint { range: 0x0000..=0x3FFF, kind: WatchKind::Write };
+        mem.watchpoints.toggle(write.clone());
+        mem.write(0x0100, 0x01);
+        assert_eq!(mem.watchpoints.take_hits(), vec![WatchHit { addr: 0x0100, watchpoint: write, old: 0x00, new: 0x00 }]);
+    }
+
     #[test]
     fn test_memory_index() {
         let addr: u16 = 0x0000;
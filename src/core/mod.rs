@@ -1,4 +1,12 @@
 pub mod register;
 pub mod memory;
 pub mod opcodes;
-pub mod table_builder;
\ No newline at end of file
+pub mod interrupts;
+pub mod decoder;
+pub mod info;
+pub mod assembler;
+pub mod watchpoints;
+pub mod callstack;
+pub mod trace;
+pub mod state;
+pub mod bess;
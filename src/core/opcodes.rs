@@ -73,7 +73,6 @@ pub enum Instruction {
     
     // CUSTOM
     XXX,    // unknown
-    TODO,   // to be done
 }
 
 /// TODO - simplify
@@ -122,9 +121,8 @@ impl Instruction {
             Instruction::EI =>                                              format!("EI"),
             Instruction::RETI =>                                            format!("RETI"),
             Instruction::RST(op) =>                                    format!("RST 0x{:X}", op),
-            Instruction::PREFIX =>                                          format!("RETI"),
+            Instruction::PREFIX =>                                          format!("PREFIX CB"),
             Instruction::XXX =>                                             format!("???"),
-            Instruction::TODO =>                                            format!("TODO"),
             Instruction::JP(op1, op2) => {
                 if let InstrucionTarget::Blank = op1 {
                     format!("JP {}", op2)
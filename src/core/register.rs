@@ -35,11 +35,11 @@ impl Register {
     }
 
     pub fn inc(&mut self) {
-        self.value += 1;
+        self.value = self.value.wrapping_add(1);
     }
 
     pub fn dec(&mut self){
-        self.value -= 1;
+        self.value = self.value.wrapping_sub(1);
     }
 }
 
@@ -0,0 +1,304 @@
+use std::{collections::HashMap, fmt, fs, io, path::Path};
+
+/*
+    Save state format
+
+    8 byte magic "GBEMUSAV", u16 format version, then blocks until END:
+        4 byte ASCII tag, u32 payload length, payload
+    Numbers are little endian.
+
+    Blocks:
+        "CPU " - AF BC DE HL SP PC, address of the last instruction(u16 each),
+                 state bits(IME, EI pending, HALT, STOP from bit 0),
+                 cycles left of the current instruction, DIV and TIMA
+                 counters(i32 each)
+        "MBC " - mapped ROM bank(u16)
+        "ROM " - cartridge hash(u32) and size(u32), states only load on the
+                 same cartridge
+        "MEM " - whole 64KiB address space, holds IE/IF with pending interrupts
+        "JOYP" - held joypad buttons(u8), optional
+        "VRAM" - CGB VRAM bank that isn't mapped at 0x8000, 8KiB, only
+                 written for CGB cartridges
+        "END " - empty, always last
+
+    Blocks with unknown tags are skipped, so components that aren't emulated
+    yet(PPU, APU) get blocks of their own without a version change. The version
+    goes up when layout of an existing block changes, `migrate` converts older
+    blocks to the current layout.
+*/
+
+const MAGIC: &[u8; 8] = b"GBEMUSAV";
+pub const STATE_VERSION: u16 = 1;
+
+#[derive(Debug)]
+pub enum StateError {
+    Io(io::Error),
+    /// Data is truncated or a block is malformed
+    Invalid(String),
+    /// State was written by a newer version of the emulator
+    UnsupportedVersion(u16),
+    /// State was saved with another cartridge
+    WrongRom,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::Io(e) => write!(f, "{}", e),
+            StateError::Invalid(reason) => write!(f, "invalid save state: {}", reason),
+            StateError::UnsupportedVersion(v) => write!(f, "save state version {} is not supported", v),
+            StateError::WrongRom => write!(f, "save state belongs to another ROM"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+impl From<io::Error> for StateError {
+    fn from(e: io::Error) -> StateError {
+        StateError::Io(e)
+    }
+}
+
+/// ## Machine snapshot
+/// Everything needed to resume execution exactly where it was saved, except
+/// the cartridge itself which is identified by `rom_hash`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MachineState {
+    pub af: u16,
+    pub bc: u16,
+    pub de: u16,
+    pub hl: u16,
+    pub sp: u16,
+    pub pc: u16,
+    pub instruction_pc: u16,
+
+    pub ime: bool,
+    pub ei_pending: bool,
+    pub halted: bool,
+    pub stopped: bool,
+
+    pub cycles: i32,
+    pub div_cycles: i32,
+    pub tima_cycles: i32,
+
+    pub rom_bank: u16,
+    pub rom_hash: u32,
+    pub rom_size: u32,
+
+    /// Whole address space
+    pub memory: Vec<u8>,
+    /// Unmapped CGB VRAM bank, empty on DMG
+    pub other_vram: Vec<u8>,
+    /// Held joypad buttons
+    pub buttons: u8,
+}
+
+/// FNV-1a hash identifying a cartridge image
+pub fn rom_hash(rom: &[u8]) -> u32 {
+    rom.iter().fold(0x811C_9DC5, |hash, b| (hash ^ *b as u32).wrapping_mul(0x0100_0193))
+}
+
+/// Little endian reader over a block payload, `tag` names the block in errors
+pub struct Reader<'a> {
+    pub tag: &'a str,
+    pub data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn take(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        if self.data.len() < len {
+            return Err(StateError::Invalid(format!("{} block is too short", self.tag)));
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, StateError> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, StateError> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub fn i32(&mut self) -> Result<i32, StateError> {
+        Ok(self.u32()? as i32)
+    }
+}
+
+pub fn write_block(out: &mut Vec<u8>, tag: &[u8; 4], payload: &[u8]) {
+    out.extend_from_slice(tag);
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(payload);
+}
+
+/// Converts blocks of a state saved by an older version to the current layout
+fn migrate(version: u16, _blocks: &mut HashMap<[u8; 4], Vec<u8>>) -> Result<(), StateError> {
+    match version {
+        STATE_VERSION => Ok(()),
+        // no older layouts exist yet, every version before the current one
+        // gets a conversion step here
+        v => Err(StateError::UnsupportedVersion(v)),
+    }
+}
+
+impl MachineState {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.memory.len() + 128);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&STATE_VERSION.to_le_bytes());
+
+        let mut cpu = Vec::new();
+        for word in [self.af, self.bc, self.de, self.hl, self.sp, self.pc, self.instruction_pc] {
+            cpu.extend_from_slice(&word.to_le_bytes());
+        }
+        let bits = [self.ime, self.ei_pending, self.halted, self.stopped]
+            .iter()
+            .enumerate()
+            .fold(0u8, |bits, (i, set)| bits | (*set as u8) << i);
+        cpu.push(bits);
+        for counter in [self.cycles, self.div_cycles, self.tima_cycles] {
+            cpu.extend_from_slice(&counter.to_le_bytes());
+        }
+        write_block(&mut out, b"CPU ", &cpu);
+        write_block(&mut out, b"MBC ", &self.rom_bank.to_le_bytes());
+
+        let mut rom = Vec::new();
+        rom.extend_from_slice(&self.rom_hash.to_le_bytes());
+        rom.extend_from_slice(&self.rom_size.to_le_bytes());
+        write_block(&mut out, b"ROM ", &rom);
+        write_block(&mut out, b"MEM ", &self.memory);
+        write_block(&mut out, b"JOYP", &[self.buttons]);
+        if !self.other_vram.is_empty() {
+            write_block(&mut out, b"VRAM", &self.other_vram);
+        }
+        write_block(&mut out, b"END ", &[]);
+        out
+    }
+
+    pub fn decode(data: &[u8]) -> Result<MachineState, StateError> {
+        let mut reader = Reader { tag: "header", data };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(StateError::Invalid("not a save state".to_string()));
+        }
+        let version = reader.u16()?;
+        if version > STATE_VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+
+        let mut blocks = HashMap::new();
+        loop {
+            reader.tag = "block header";
+            let t = reader.take(4)?;
+            let tag = [t[0], t[1], t[2], t[3]];
+            let len = reader.u32()? as usize;
+            reader.tag = "block";
+            let payload = reader.take(len)?;
+            if &tag == b"END " {
+                break;
+            }
+            blocks.insert(tag, payload.to_vec());
+        }
+        migrate(version, &mut blocks)?;
+
+        let block = |tag: &'static [u8; 4]| {
+            let name = std::str::from_utf8(tag).unwrap_or_default();
+            blocks
+                .get(tag)
+                .map(|data| Reader { tag: name, data })
+                .ok_or_else(|| StateError::Invalid(format!("missing {} block", name.trim_end())))
+        };
+
+        let mut cpu = block(b"CPU ")?;
+        let (af, bc, de, hl) = (cpu.u16()?, cpu.u16()?, cpu.u16()?, cpu.u16()?);
+        let (sp, pc, instruction_pc) = (cpu.u16()?, cpu.u16()?, cpu.u16()?);
+        let bits = cpu.u8()?;
+        let (cycles, div_cycles, tima_cycles) = (cpu.i32()?, cpu.i32()?, cpu.i32()?);
+        let rom_bank = block(b"MBC ")?.u16()?;
+        let mut rom = block(b"ROM ")?;
+        let (rom_hash, rom_size) = (rom.u32()?, rom.u32()?);
+        let memory = block(b"MEM ")?.data.to_vec();
+        if memory.len() != 0x10000 {
+            return Err(StateError::Invalid(format!("MEM block is {} bytes", memory.len())));
+        }
+        let other_vram = blocks.get(b"VRAM").cloned().unwrap_or_default();
+        let buttons = match block(b"JOYP") {
+            Ok(mut joypad) => joypad.u8()?,
+            Err(_) => 0,
+        };
+
+        Ok(MachineState {
+            af, bc, de, hl, sp, pc, instruction_pc,
+            ime: bits & 0x01 != 0,
+            ei_pending: bits & 0x02 != 0,
+            halted: bits & 0x04 != 0,
+            stopped: bits & 0x08 != 0,
+            cycles, div_cycles, tima_cycles,
+            rom_bank, rom_hash, rom_size,
+            memory, other_vram, buttons,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), StateError> {
+        Ok(fs::write(path, self.encode())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> MachineState {
+        let mut memory = vec![0; 0x10000];
+        memory[0xC000] = 0x42;
+        MachineState {
+            af: 0x01B0, bc: 0x0013, de: 0x00D8, hl: 0x014D, sp: 0xFFFE, pc: 0x0100, instruction_pc: 0x00FF,
+            ime: true, ei_pending: false, halted: true, stopped: false,
+            cycles: 3, div_cycles: 200, tima_cycles: -1,
+            rom_bank: 1, rom_hash: rom_hash(&[1, 2, 3]), rom_size: 3,
+            memory,
+            other_vram: Vec::new(),
+            buttons: 0x81,
+        }
+    }
+
+    #[test]
+    fn test_encode() {
+        let state = state();
+        let data = state.encode();
+        assert_eq!(&data[..8], MAGIC);
+        assert_eq!(MachineState::decode(&data).unwrap(), state);
+        let cgb = MachineState { other_vram: vec![0x42; 0x2000], ..state.clone() };
+        assert_eq!(MachineState::decode(&cgb.encode()).unwrap(), cgb);
+
+        assert!(matches!(MachineState::decode(&data[..data.len() - 4]), Err(StateError::Invalid(_))));
+        let mut newer = data.clone();
+        newer[8] = 0xFF;
+        assert!(matches!(MachineState::decode(&newer), Err(StateError::UnsupportedVersion(0x00FF))));
+    }
+
+    #[test]
+    fn test_unknown_blocks() {
+        let state = state();
+        let data = state.encode();
+        // block from a newer version inserted after the header
+        let mut extended = data[..10].to_vec();
+        write_block(&mut extended, b"PPU ", &[1, 2, 3, 4]);
+        extended.extend_from_slice(&data[10..]);
+        assert_eq!(MachineState::decode(&extended).unwrap(), state);
+
+        // CPU block removed
+        let cpu_len = u32::from_le_bytes([data[14], data[15], data[16], data[17]]) as usize;
+        let mut missing = data[..10].to_vec();
+        missing.extend_from_slice(&data[10 + 8 + cpu_len..]);
+        assert!(matches!(MachineState::decode(&missing), Err(StateError::Invalid(_))));
+    }
+}
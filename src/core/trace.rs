@@ -0,0 +1,218 @@
+use std::{fmt, fs::File, io::{self, BufWriter, Write}, ops::RangeInclusive, path::Path, str::FromStr};
+
+/*
+    gameboy-doctor trace format
+
+    One line per executed instruction with the state before it runs:
+        A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02
+    PCMEM holds 4 bytes at PC. Interrupt dispatch and halted cycles aren't
+    instructions so they produce no lines.
+*/
+
+/// CPU state of a single trace line
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TraceLine {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+    pub pcmem: [u8; 4],
+}
+
+/// Flag names with their masks in F
+const FLAGS: [(&str, u8); 4] = [("Z", 0x80), ("N", 0x40), ("H", 0x20), ("C", 0x10)];
+
+impl TraceLine {
+    /// Describes fields of `actual` that don't match these, flags are compared
+    /// one by one
+    pub fn differences(&self, actual: &TraceLine) -> Vec<String> {
+        let mut diffs = Vec::new();
+        let bytes = [
+            ("A", self.a, actual.a),
+            ("B", self.b, actual.b),
+            ("C", self.c, actual.c),
+            ("D", self.d, actual.d),
+            ("E", self.e, actual.e),
+            ("H", self.h, actual.h),
+            ("L", self.l, actual.l),
+        ];
+        for (name, expected, got) in bytes {
+            if expected != got {
+                diffs.push(format!("{}: expected ${:02X}, got ${:02X}", name, expected, got));
+            }
+        }
+        for (name, mask) in FLAGS {
+            let (expected, got) = (self.f & mask != 0, actual.f & mask != 0);
+            if expected != got {
+                diffs.push(format!("flag {}: expected {}, got {}", name, expected as u8, got as u8));
+            }
+        }
+        for (name, expected, got) in [("SP", self.sp, actual.sp), ("PC", self.pc, actual.pc)] {
+            if expected != got {
+                diffs.push(format!("{}: expected ${:04X}, got ${:04X}", name, expected, got));
+            }
+        }
+        if self.pcmem != actual.pcmem {
+            diffs.push(format!("PCMEM: expected {:02X?}, got {:02X?}", self.pcmem, actual.pcmem));
+        }
+        diffs
+    }
+}
+
+impl fmt::Display for TraceLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+            self.a, self.f, self.b, self.c, self.d, self.e, self.h, self.l, self.sp, self.pc,
+            self.pcmem[0], self.pcmem[1], self.pcmem[2], self.pcmem[3],
+        )
+    }
+}
+
+impl FromStr for TraceLine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<TraceLine, String> {
+        let mut line = TraceLine::default();
+        let mut fields = 0;
+        for field in s.split_whitespace() {
+            let (name, value) = field.split_once(':').ok_or_else(|| format!("invalid field {}", field))?;
+            let byte = || u8::from_str_radix(value, 16).map_err(|_| format!("invalid value {}", field));
+            let word = || u16::from_str_radix(value, 16).map_err(|_| format!("invalid value {}", field));
+            match name {
+                "A" => line.a = byte()?,
+                "F" => line.f = byte()?,
+                "B" => line.b = byte()?,
+                "C" => line.c = byte()?,
+                "D" => line.d = byte()?,
+                "E" => line.e = byte()?,
+                "H" => line.h = byte()?,
+                "L" => line.l = byte()?,
+                "SP" => line.sp = word()?,
+                "PC" => line.pc = word()?,
+                "PCMEM" => {
+                    let bytes: Vec<&str> = value.split(',').collect();
+                    if bytes.len() != 4 {
+                        return Err(format!("invalid value {}", field));
+                    }
+                    for (place, byte) in line.pcmem.iter_mut().zip(bytes) {
+                        *place = u8::from_str_radix(byte, 16).map_err(|_| format!("invalid value {}", field))?;
+                    }
+                },
+                _ => return Err(format!("unknown field {}", name)),
+            }
+            fields += 1;
+        }
+        match fields {
+            11 => Ok(line),
+            _ => Err(format!("expected 11 fields, got {}", fields)),
+        }
+    }
+}
+
+/// ## Instruction trace sink
+/// Writes a line for every instruction executed in `range`, until `limit` lines
+/// are written. A failed write stops the trace, the error is kept in `error`
+pub struct Trace {
+    out: Box<dyn Write>,
+    pub range: RangeInclusive<u16>,
+    pub limit: Option<usize>,
+    lines: usize,
+    pub error: Option<io::Error>,
+}
+
+impl Trace {
+    pub fn new(out: Box<dyn Write>) -> Trace {
+        Trace { out, range: 0x0000..=0xFFFF, limit: None, lines: 0, error: None }
+    }
+
+    pub fn create(path: &Path) -> io::Result<Trace> {
+        Ok(Trace::new(Box::new(BufWriter::new(File::create(path)?))))
+    }
+
+    /// Whether the instruction at `pc` should be written
+    pub fn wants(&self, pc: u16) -> bool {
+        self.range.contains(&pc) && !self.is_done()
+    }
+
+    /// Limit is reached or writing failed, nothing more will be written
+    pub fn is_done(&self) -> bool {
+        self.error.is_some() || self.limit.is_some_and(|limit| self.lines >= limit)
+    }
+
+    pub fn lines(&self) -> usize {
+        self.lines
+    }
+
+    pub fn record(&mut self, line: &TraceLine) {
+        if !self.wants(line.pc) {
+            return;
+        }
+        match writeln!(self.out, "{}", line) {
+            Ok(()) => self.lines += 1,
+            Err(e) => self.error = Some(e),
+        }
+        if self.is_done() {
+            self.flush();
+        }
+    }
+
+    pub fn flush(&mut self) {
+        if let Err(e) = self.out.flush() {
+            self.error.get_or_insert(e);
+        }
+    }
+}
+
+impl Drop for Trace {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINE: &str = "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02";
+
+    #[test]
+    fn test_format() {
+        let line: TraceLine = LINE.parse().unwrap();
+        assert_eq!(line.f, 0xB0);
+        assert_eq!(line.sp, 0xFFFE);
+        assert_eq!(line.pcmem, [0x00, 0xC3, 0x13, 0x02]);
+        assert_eq!(line.to_string(), LINE);
+
+        let actual = TraceLine { a: 0x02, f: 0x30, ..line };
+        assert_eq!(line.differences(&actual), vec!["A: expected $01, got $02", "flag Z: expected 1, got 0"]);
+        assert!(line.differences(&line).is_empty());
+
+        assert!("A:01 F:B0".parse::<TraceLine>().is_err());
+        assert!(LINE.replace("PC:0100", "PC:01G0").parse::<TraceLine>().is_err());
+        assert!(LINE.replace("PCMEM:00,C3,13,02", "PCMEM:00,C3").parse::<TraceLine>().is_err());
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut trace = Trace::new(Box::new(io::sink()));
+        trace.range = 0x0100..=0x3FFF;
+        trace.limit = Some(2);
+        let at = |pc| TraceLine { pc, ..TraceLine::default() };
+        trace.record(&at(0x0038));
+        trace.record(&at(0x0100));
+        assert_eq!(trace.lines(), 1);
+        assert!(!trace.wants(0x4000));
+        trace.record(&at(0x0101));
+        trace.record(&at(0x0102));
+        assert_eq!(trace.lines(), 2);
+        assert!(trace.is_done());
+    }
+}
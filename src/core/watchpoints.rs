@@ -0,0 +1,137 @@
+use std::{fmt, ops::RangeInclusive};
+
+/// Kind of memory access a watchpoint triggers on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WatchKind {
+    Read,
+    Write,
+    /// Write that changes the stored value
+    Change,
+}
+
+impl fmt::Display for WatchKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchKind::Read => write!(f, "read"),
+            WatchKind::Write => write!(f, "write"),
+            WatchKind::Change => write!(f, "change"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Watchpoint {
+    pub range: RangeInclusive<u16>,
+    pub kind: WatchKind,
+}
+
+/// Access that triggered a watchpoint. For reads `old` and `new` are the same
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WatchHit {
+    pub addr: u16,
+    pub watchpoint: Watchpoint,
+    pub old: u8,
+    pub new: u8,
+}
+
+/// ## Memory watchpoints
+/// Checked by `Memory::read` and `Memory::write`. Hits are kept until they are
+/// taken, so execution can be stopped at the instruction that caused them. Every
+/// watchpoint keeps only its first hit, so the list stays bounded.
+#[derive(Clone, Debug, Default)]
+pub struct Watchpoints {
+    list: Vec<Watchpoint>,
+    hits: Vec<WatchHit>,
+}
+
+impl Watchpoints {
+    pub fn new() -> Watchpoints {
+        Watchpoints::default()
+    }
+
+    /// Adds watchpoint or removes the same existing one, returns true if it was added
+    pub fn toggle(&mut self, watchpoint: Watchpoint) -> bool {
+        if let Some(i) = self.list.iter().position(|w| *w == watchpoint) {
+            self.list.remove(i);
+            return false;
+        }
+        self.list.push(watchpoint);
+        true
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Watchpoint> {
+        self.list.iter()
+    }
+
+    /// Takes the recorded hits, in order of access
+    pub fn take_hits(&mut self) -> Vec<WatchHit> {
+        std::mem::take(&mut self.hits)
+    }
+
+    pub fn on_read(&mut self, addr: u16, value: u8) {
+        self.check(addr, WatchKind::Read, value, value);
+    }
+
+    pub fn on_write(&mut self, addr: u16, old: u8, new: u8) {
+        let kind = if old != new { WatchKind::Change } else { WatchKind::Write };
+        self.check(addr, kind, old, new);
+    }
+
+    fn check(&mut self, addr: u16, access: WatchKind, old: u8, new: u8) {
+        let triggered = self.list.iter().filter(|w| {
+            w.range.contains(&addr) && match w.kind {
+                WatchKind::Read => access == WatchKind::Read,
+                WatchKind::Write => access != WatchKind::Read,
+                WatchKind::Change => access == WatchKind::Change,
+            }
+        });
+        for w in triggered {
+            if !self.hits.iter().any(|hit| hit.watchpoint == *w) {
+                self.hits.push(WatchHit { addr, watchpoint: w.clone(), old, new });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watch_kinds() {
+        let mut watch = Watchpoints::new();
+        let change = Watchpoint { range: 0xC000..=0xC00F, kind: WatchKind::Change };
+        watch.toggle(change.clone());
+        watch.on_read(0xC000, 1);
+        watch.on_write(0xC001, 1, 1);
+        watch.on_write(0xC010, 1, 2);
+        assert_eq!(watch.take_hits(), vec![]);
+
+        watch.on_write(0xC00F, 1, 2);
+        watch.on_write(0xC00E, 3, 4);
+        assert_eq!(watch.take_hits(), vec![WatchHit { addr: 0xC00F, watchpoint: change.clone(), old: 1, new: 2 }]);
+        assert_eq!(watch.take_hits(), vec![]);
+
+        let write = Watchpoint { range: 0xFF80..=0xFF80, kind: WatchKind::Write };
+        watch.toggle(write.clone());
+        watch.on_write(0xFF80, 5, 5);
+        assert_eq!(watch.take_hits(), vec![WatchHit { addr: 0xFF80, watchpoint: write.clone(), old: 5, new: 5 }]);
+
+        assert!(!watch.toggle(write));
+        watch.on_write(0xFF80, 5, 5);
+        assert_eq!(watch.take_hits(), vec![]);
+
+        // overlapping watchpoints are all reported
+        let write = Watchpoint { range: 0xC008..=0xC008, kind: WatchKind::Write };
+        watch.toggle(write.clone());
+        watch.on_write(0xC008, 1, 2);
+        assert_eq!(watch.take_hits(), vec![
+            WatchHit { addr: 0xC008, watchpoint: change, old: 1, new: 2 },
+            WatchHit { addr: 0xC008, watchpoint: write, old: 1, new: 2 },
+        ]);
+    }
+}
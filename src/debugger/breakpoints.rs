@@ -0,0 +1,255 @@
+use std::{collections::BTreeMap, fmt, ops::RangeInclusive};
+
+use crate::{
+    core::{memory::Memory, watchpoints::{Watchpoint, WatchKind}},
+    cpu::CPU,
+    disasm::{Location, symbols::Symbols},
+};
+
+use super::expr::{Expr, ExprError};
+
+/// Parses location entered by user: label name, `BB:AAAA`, or address as
+/// `$AAAA`, `0xAAAA`, `AAAA`(always hex). Addresses without a bank get the bank
+/// currently mapped in `memory`
+pub fn parse_location(text: &str, symbols: &Symbols, memory: &Memory) -> Option<Location> {
+    let text = text.trim();
+    if let Some(location) = symbols.location(text) {
+        return Some(location);
+    }
+    let hex = |s: &str| {
+        let s = s.strip_prefix('$').or_else(|| s.strip_prefix("0x")).unwrap_or(s);
+        u16::from_str_radix(s, 16).ok()
+    };
+    match text.split_once(':') {
+        Some((bank, addr)) => Some(Location::normalized(hex(bank)?, hex(addr)?)),
+        None => Some(Location::mapped(memory, hex(text)?)),
+    }
+}
+
+/// Parses watchpoint entered by user: `start[-end] [r|w|c]`, where `start` and `end`
+/// are locations as accepted by `parse_location`. Watches writes by default
+pub fn parse_watchpoint(text: &str, symbols: &Symbols, memory: &Memory) -> Option<Watchpoint> {
+    let mut parts = text.split_whitespace();
+    let range = parts.next()?;
+    let kind = match parts.next() {
+        None | Some("w") => WatchKind::Write,
+        Some("r") => WatchKind::Read,
+        Some("c") => WatchKind::Change,
+        Some(_) => return None,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(Watchpoint { range: parse_range(range, symbols, memory)?, kind })
+}
+
+/// Parses address range entered as `start[-end]`
+pub fn parse_range(text: &str, symbols: &Symbols, memory: &Memory) -> Option<RangeInclusive<u16>> {
+    let (start, end) = match text.split_once('-') {
+        Some((start, end)) => (start, end),
+        None => (text, text),
+    };
+    let start = parse_location(start, symbols, memory)?.addr;
+    let end = parse_location(end, symbols, memory)?.addr;
+    if end < start {
+        return None;
+    }
+    Some(start..=end)
+}
+
+/// Splits `text` before the first `if` or `count` word, the rest is parsed by
+/// `Condition::parse`
+pub fn split_condition(text: &str) -> (&str, &str) {
+    let mut offset = 0;
+    for word in text.split_inclusive(char::is_whitespace) {
+        if matches!(word.trim_end(), "if" | "count") {
+            break;
+        }
+        offset += word.len();
+    }
+    (text[..offset].trim(), &text[offset..])
+}
+
+/// Condition and hit count attached to a breakpoint or watchpoint
+#[derive(Clone, Debug, Default)]
+pub struct Condition {
+    /// Expression as entered and its parsed form
+    pub expr: Option<(String, Expr)>,
+    /// Times the condition has to be met before execution stops
+    pub hit_count: Option<u32>,
+    /// Times the condition was met
+    pub hits: u32,
+}
+
+impl Condition {
+    /// Parses `[if <expr>] [count N]`, empty text gives a condition that is always met
+    pub fn parse(text: &str, symbols: &Symbols) -> Result<Condition, String> {
+        let mut text = text.trim();
+        let mut condition = Condition::default();
+        let words: Vec<&str> = text.split_whitespace().collect();
+        if let [.., "count", n] = words.as_slice() {
+            let n = n.parse().map_err(|_| format!("invalid hit count {}", n))?;
+            condition.hit_count = Some(n);
+            text = text[..text.rfind("count").unwrap()].trim_end();
+        }
+        if text.is_empty() {
+            return Ok(condition);
+        }
+        let expr = text.strip_prefix("if")
+            .filter(|e| e.starts_with(char::is_whitespace))
+            .ok_or_else(|| format!("expected `if` or `count`, got {}", text))?
+            .trim();
+        let parsed = Expr::parse_condition(expr, symbols).map_err(|e| e.to_string())?;
+        condition.expr = Some((expr.to_string(), parsed));
+        Ok(condition)
+    }
+
+    /// Evaluates the condition when its breakpoint is reached, returns true if
+    /// execution should stop
+    pub fn check(&mut self, cpu: &CPU) -> Result<bool, ExprError> {
+        if let Some((_, expr)) = &self.expr {
+            if expr.eval(cpu)? == 0 {
+                return Ok(false);
+            }
+        }
+        self.hits += 1;
+        Ok(self.hits >= self.hit_count.unwrap_or(0))
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((text, _)) = &self.expr {
+            write!(f, " if {}", text)?;
+        }
+        match self.hit_count {
+            Some(count) => write!(f, " [{}/{}]", self.hits, count),
+            None if self.hits > 0 => write!(f, " [{}]", self.hits),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Set of execution breakpoints. Breakpoints in switchable ROM are hit only
+/// when their bank is mapped
+#[derive(Clone, Debug, Default)]
+pub struct Breakpoints {
+    locations: BTreeMap<Location, Condition>,
+}
+
+impl Breakpoints {
+    pub fn new() -> Breakpoints {
+        Breakpoints::default()
+    }
+
+    /// Adds breakpoint or removes an existing one, returns true if it was added
+    pub fn toggle(&mut self, location: Location) -> bool {
+        if self.locations.remove(&location).is_some() {
+            return false;
+        }
+        self.locations.insert(location, Condition::default());
+        true
+    }
+
+    /// Adds breakpoint or replaces condition of an existing one
+    pub fn set(&mut self, location: Location, condition: Condition) {
+        self.locations.insert(location, condition);
+    }
+
+    pub fn contains(&self, location: Location) -> bool {
+        self.locations.contains_key(&location)
+    }
+
+    /// Returns true if execution at current PC should stop
+    pub fn check(&mut self, cpu: &CPU) -> Result<bool, ExprError> {
+        match self.locations.get_mut(&Location::mapped(&cpu.memory, cpu.pc.value)) {
+            Some(condition) => condition.check(cpu),
+            None => Ok(false),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Location, &Condition)> {
+        self.locations.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_location() {
+        let mem = Memory::new();
+        let mut symbols = Symbols::new();
+        symbols.insert(Location::new(2, 0x4100), "UpdateSprites");
+
+        assert_eq!(parse_location("UpdateSprites", &symbols, &mem), Some(Location::new(2, 0x4100)));
+        assert_eq!(parse_location("$0150", &symbols, &mem), Some(Location::new(0, 0x0150)));
+        assert_eq!(parse_location("0x4000", &symbols, &mem), Some(Location::new(1, 0x4000)));
+        assert_eq!(parse_location("03:4000", &symbols, &mem), Some(Location::new(3, 0x4000)));
+        assert_eq!(parse_location("03:C000", &symbols, &mem), Some(Location::new(0, 0xC000)));
+        assert_eq!(parse_location("Missing", &symbols, &mem), None);
+    }
+
+    #[test]
+    fn test_parse_watchpoint() {
+        let mem = Memory::new();
+        let mut symbols = Symbols::new();
+        symbols.insert(Location::new(0, 0xC100), "wCounter");
+
+        assert_eq!(
+            parse_watchpoint("wCounter", &symbols, &mem),
+            Some(Watchpoint { range: 0xC100..=0xC100, kind: WatchKind::Write })
+        );
+        assert_eq!(
+            parse_watchpoint("$C000-$C0FF c", &symbols, &mem),
+            Some(Watchpoint { range: 0xC000..=0xC0FF, kind: WatchKind::Change })
+        );
+        assert_eq!(parse_watchpoint("C0FF-C000", &symbols, &mem), None);
+        assert_eq!(parse_watchpoint("C000 x", &symbols, &mem), None);
+    }
+
+    #[test]
+    fn test_banked_breakpoint() {
+        let mut cpu = CPU::new();
+        let mut breakpoints = Breakpoints::new();
+        assert!(breakpoints.toggle(Location::new(2, 0x4000)));
+        assert!(breakpoints.toggle(Location::new(0, 0x0150)));
+        // bank 1 is mapped
+        cpu.pc.value = 0x4000;
+        assert_eq!(breakpoints.check(&cpu), Ok(false));
+        cpu.pc.value = 0x0150;
+        assert_eq!(breakpoints.check(&cpu), Ok(true));
+        assert!(!breakpoints.toggle(Location::new(0, 0x0150)));
+        assert_eq!(breakpoints.check(&cpu), Ok(false));
+    }
+
+    #[test]
+    fn test_condition() {
+        let symbols = Symbols::new();
+        assert_eq!(split_condition("$C000-$C0FF c if A == 1"), ("$C000-$C0FF c", "if A == 1"));
+        assert_eq!(split_condition("Main count 3"), ("Main", "count 3"));
+        assert_eq!(split_condition("Main"), ("Main", ""));
+
+        let mut cpu = CPU::new();
+        let mut breakpoints = Breakpoints::new();
+        let condition = Condition::parse("if [HL] == $42 count 2", &symbols).unwrap();
+        assert_eq!(condition.to_string(), " if [HL] == $42 [0/2]");
+        breakpoints.set(Location::new(0, 0x0150), condition);
+        cpu.pc.value = 0x0150;
+        cpu.reg_hl.value = 0xC000;
+        assert_eq!(breakpoints.check(&cpu), Ok(false));
+        cpu.memory[0xC000u16] = 0x42;
+        assert_eq!(breakpoints.check(&cpu), Ok(false));
+        assert_eq!(breakpoints.check(&cpu), Ok(true));
+
+        breakpoints.set(Location::new(0, 0x0150), Condition::parse("if 1 / [HL]", &symbols).unwrap());
+        cpu.memory[0xC000u16] = 0;
+        assert_eq!(breakpoints.check(&cpu), Err(ExprError::DivisionByZero));
+
+        assert!(Condition::parse("count", &symbols).is_err());
+        assert!(Condition::parse("A == 1", &symbols).is_err());
+        assert!(Condition::parse("if", &symbols).is_err());
+        assert!(Condition::parse("if A ==", &symbols).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+use std::{collections::VecDeque, io::BufRead};
+
+use crate::{core::trace::TraceLine, cpu::CPU};
+
+/// Instructions shown before a divergence when no amount is given
+pub const CONTEXT_LINES: usize = 8;
+
+/// Steps without executing an instruction after which the CPU is considered stuck,
+/// about a second of HALT
+const IDLE_LIMIT: u32 = 1_000_000;
+
+/// First trace line that doesn't match the CPU state
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Line number in the reference trace, starting from 1
+    pub line: usize,
+    pub expected: TraceLine,
+    pub actual: TraceLine,
+    /// Matching lines before the divergence, oldest first
+    pub history: Vec<TraceLine>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    /// Every line matched, holds the amount of instructions compared
+    Matched(usize),
+    Diverged(Divergence),
+}
+
+/// Steps `cpu` comparing its state before every instruction with lines of a
+/// gameboy-doctor trace. Stops at the first mismatch, leaving the CPU before the
+/// mismatched instruction. Blank lines are skipped, `context` lines before the
+/// divergence are kept. \
+/// gameboy-doctor logs are made with LY always reading $90, there is no PPU
+/// updating LY yet so ROMs polling it diverge early unless it is set with `write`
+pub fn compare(cpu: &mut CPU, reference: impl BufRead, context: usize) -> Result<Comparison, String> {
+    let mut history = VecDeque::with_capacity(context + 1);
+    let mut compared = 0;
+    for (i, text) in reference.lines().enumerate() {
+        let text = text.map_err(|e| e.to_string())?;
+        if text.trim().is_empty() {
+            continue;
+        }
+        let expected: TraceLine = text.parse().map_err(|e| format!("line {}: {}", i + 1, e))?;
+
+        let mut idle = 0;
+        while !cpu.executes_next() {
+            cpu.step();
+            idle += 1;
+            if idle == IDLE_LIMIT {
+                return Err(format!("CPU stopped executing instructions before line {}", i + 1));
+            }
+        }
+
+        let actual = cpu.trace_line();
+        if actual != expected {
+            return Ok(Comparison::Diverged(Divergence {
+                line: i + 1,
+                expected,
+                actual,
+                history: history.into(),
+            }));
+        }
+        history.push_back(actual);
+        if history.len() > context {
+            history.pop_front();
+        }
+        compared += 1;
+        cpu.step();
+    }
+    Ok(Comparison::Matched(compared))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program() -> CPU {
+        let mut cpu = CPU::new();
+        // INC A; INC A; NOP
+        cpu.memory[0x0100u16] = 0x3C;
+        cpu.memory[0x0101u16] = 0x3C;
+        cpu
+    }
+
+    /// Trace of `program` as a reference log
+    fn reference(steps: usize) -> Vec<TraceLine> {
+        let mut cpu = program();
+        (0..steps)
+            .map(|_| {
+                let line = cpu.trace_line();
+                cpu.step();
+                line
+            })
+            .collect()
+    }
+
+    fn text(lines: &[TraceLine]) -> String {
+        lines.iter().map(|l| format!("{}\n", l)).collect()
+    }
+
+    #[test]
+    fn test_matched() {
+        let log = text(&reference(3));
+        assert_eq!(compare(&mut program(), log.as_bytes(), 2), Ok(Comparison::Matched(3)));
+    }
+
+    #[test]
+    fn test_diverged() {
+        let mut lines = reference(3);
+        lines[2].a = 0x10;
+        let mut cpu = program();
+        let divergence = match compare(&mut cpu, text(&lines).as_bytes(), 1) {
+            Ok(Comparison::Diverged(d)) => d,
+            result => panic!("unexpected {:?}", result),
+        };
+        assert_eq!(divergence.line, 3);
+        assert_eq!(divergence.actual.a, 0x03);
+        assert_eq!(divergence.history, lines[1..2].to_vec());
+        assert_eq!(cpu.pc.value, 0x0102);
+
+        assert!(compare(&mut program(), "A:01".as_bytes(), 1).is_err());
+    }
+}
@@ -0,0 +1,328 @@
+/// Lines of output kept in the console
+const LOG_SIZE: usize = 256;
+
+/// Command names with their arguments and description, used by `help` and completion
+pub const COMMANDS: [(&str, &str); 24] = [
+    ("break", "<location> [if <expr>] [count N] - toggle or set breakpoint, C and H are flags in <expr>"),
+    ("watch", "<start>[-<end>] [r|w|c] [if <expr>] [count N] - toggle watchpoint"),
+    ("step", "[N] - execute N instructions"),
+    ("continue", "- run until a breakpoint is hit"),
+    ("next", "- step over call at PC"),
+    ("finish", "- run until the current function returns"),
+    ("until", "<location> - run until location is reached"),
+    ("rewind", "[N] - go back N frames, 1 by default"),
+    ("reverse-step", "[N] - go back N instructions, 1 by default"),
+    ("reverse-continue", "- run backward to the previous breakpoint or watchpoint hit"),
+    ("press", "[a|b|select|start|right|left|up|down]... - hold joypad buttons, none releases all"),
+    ("goto", "<location> - show location in assembly view"),
+    ("set", "<register|flag>=<expr> - change register or flag"),
+    ("write", "<location> <expr>... - write bytes to memory"),
+    ("dump", "<location> [length] - print bytes in memory"),
+    ("print", "<expr> - evaluate expression"),
+    ("patch", "<instruction> - assemble instruction at PC"),
+    ("trace", "[<file> [<start>-<end>] [limit N] | off] - write executed instructions to file"),
+    ("compare", "<file> [context N] - run until state differs from a gameboy-doctor trace"),
+    ("reset", "[hard] - restart, hard reset reloads the ROM from disk"),
+    ("load-state", "[slot|file] - load emulator state or BESS file, slot 0 by default"),
+    ("save-state", "[slot|file] [bess] - save emulator state, slot 0 by default"),
+    ("export", "- export disassembly as RGBDS sources"),
+    ("help", "- list commands"),
+];
+
+/// Parsed console command, arguments are resolved by the debugger
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command<'a> {
+    Break(&'a str),
+    Watch(&'a str),
+    Step(u32),
+    Continue,
+    Next,
+    Finish,
+    Until(&'a str),
+    Rewind(u32),
+    ReverseStep(u32),
+    ReverseContinue,
+    Press(&'a str),
+    Goto(&'a str),
+    Set { target: &'a str, value: &'a str },
+    Write { location: &'a str, values: Vec<&'a str> },
+    Dump { location: &'a str, length: Option<&'a str> },
+    Print(&'a str),
+    Patch(&'a str),
+    Reset { hard: bool },
+    Trace(&'a str),
+    Compare(&'a str),
+    LoadState(&'a str),
+    SaveState(&'a str),
+    Export,
+    Help,
+}
+
+impl Command<'_> {
+    /// Whether the command edits the machine outside of emulation, load and reset
+    /// restart the rewind buffer themselves
+    pub fn edits(&self) -> bool {
+        matches!(self, Command::Set { .. } | Command::Write { .. } | Command::Patch(_) | Command::Press(_))
+    }
+
+    pub fn parse(line: &str) -> Result<Command<'_>, String> {
+        let line = line.trim();
+        let (name, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let args = args.trim();
+        let required = |args: &str| match args {
+            "" => Err(format!("{} expects arguments, see help", name)),
+            _ => Ok(()),
+        };
+        let no_args = || match args {
+            "" => Ok(()),
+            _ => Err(format!("{} takes no arguments", name)),
+        };
+        let command = match name {
+            "break" | "b" => { required(args)?; Command::Break(args) },
+            "watch" | "w" => { required(args)?; Command::Watch(args) },
+            "step" | "s" => match args {
+                "" => Command::Step(1),
+                n => Command::Step(n.parse().map_err(|_| format!("invalid step count {}", n))?),
+            },
+            "continue" | "c" => { no_args()?; Command::Continue },
+            "next" | "n" => { no_args()?; Command::Next },
+            "finish" | "f" => { no_args()?; Command::Finish },
+            "until" | "u" => { required(args)?; Command::Until(args) },
+            "rewind" => match args {
+                "" => Command::Rewind(1),
+                n => Command::Rewind(n.parse().map_err(|_| format!("invalid frame count {}", n))?),
+            },
+            "reverse-step" | "rs" => match args {
+                "" => Command::ReverseStep(1),
+                n => Command::ReverseStep(n.parse().map_err(|_| format!("invalid step count {}", n))?),
+            },
+            "reverse-continue" | "rc" => { no_args()?; Command::ReverseContinue },
+            "press" => Command::Press(args),
+            "goto" | "g" => { required(args)?; Command::Goto(args) },
+            "set" => {
+                let (target, value) = args.split_once('=').ok_or("expected <register>=<expr>")?;
+                required(value.trim())?;
+                Command::Set { target: target.trim(), value: value.trim() }
+            },
+            "write" => {
+                let mut parts = args.split_whitespace();
+                let location = parts.next().ok_or("expected location")?;
+                let values: Vec<&str> = parts.collect();
+                required(values.join(" ").as_str())?;
+                Command::Write { location, values }
+            },
+            "dump" | "x" => {
+                let mut parts = args.split_whitespace();
+                let location = parts.next().ok_or("expected location")?;
+                Command::Dump { location, length: parts.next() }
+            },
+            "print" | "p" => { required(args)?; Command::Print(args) },
+            "patch" | "a" => { required(args)?; Command::Patch(args) },
+            "trace" => Command::Trace(args),
+            "compare" => { required(args)?; Command::Compare(args) },
+            "reset" => match args {
+                "" => Command::Reset { hard: false },
+                "hard" => Command::Reset { hard: true },
+                _ => return Err(format!("invalid reset kind {}", args)),
+            },
+            "load-state" => Command::LoadState(args),
+            "save-state" => Command::SaveState(args),
+            "export" => { no_args()?; Command::Export },
+            "help" | "?" => Command::Help,
+            _ => return Err(format!("unknown command {}", name)),
+        };
+        Ok(command)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LogLine {
+    /// Command as entered
+    Input(String),
+    Output(String),
+    Error(String),
+}
+
+/// ## Debugger console
+/// Command line with history and completion, and the output of previous commands
+#[derive(Clone, Debug, Default)]
+pub struct Console {
+    pub input: String,
+    history: Vec<String>,
+    /// Entry of `history` shown while browsing with Up/Down
+    history_pos: Option<usize>,
+    log: Vec<LogLine>,
+}
+
+impl Console {
+    pub fn new() -> Console {
+        Console::default()
+    }
+
+    pub fn log(&mut self, line: LogLine) {
+        if self.log.len() == LOG_SIZE {
+            self.log.remove(0);
+        }
+        self.log.push(line);
+    }
+
+    pub fn lines(&self) -> &[LogLine] {
+        &self.log
+    }
+
+    /// Takes the entered line, remembering it in history
+    pub fn submit(&mut self) -> String {
+        let line = std::mem::take(&mut self.input);
+        self.history_pos = None;
+        if !line.trim().is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+        line
+    }
+
+    /// Replaces input with the previous history entry
+    pub fn history_prev(&mut self) {
+        let pos = match self.history_pos {
+            Some(pos) => pos.saturating_sub(1),
+            None if self.history.is_empty() => return,
+            None => self.history.len() - 1,
+        };
+        self.history_pos = Some(pos);
+        self.input = self.history[pos].clone();
+    }
+
+    /// Replaces input with the next history entry, or clears it past the last one
+    pub fn history_next(&mut self) {
+        match self.history_pos {
+            Some(pos) if pos + 1 < self.history.len() => {
+                self.history_pos = Some(pos + 1);
+                self.input = self.history[pos + 1].clone();
+            },
+            Some(_) => {
+                self.history_pos = None;
+                self.input.clear();
+            },
+            None => {},
+        }
+    }
+
+    /// Completes the last word of input with a command name, or one of `names` for
+    /// arguments. When several candidates match, input is extended to their common
+    /// prefix and candidates are listed in the log
+    pub fn complete<'a>(&mut self, names: impl Iterator<Item = &'a str>) {
+        let start = self.input.rfind(|c: char| c.is_whitespace() || "[(=,".contains(c)).map_or(0, |i| i + 1);
+        let word = &self.input[start..];
+        let mut candidates: Vec<&str> = if start == 0 {
+            COMMANDS.iter().map(|(name, _)| *name).filter(|name| name.starts_with(word)).collect()
+        } else {
+            names.filter(|name| name.starts_with(word)).collect()
+        };
+        candidates.sort_unstable();
+        candidates.dedup();
+        match candidates.as_slice() {
+            [] => {},
+            [name] => {
+                let name = name.to_string();
+                self.input.truncate(start);
+                self.input.push_str(&name);
+                if start == 0 {
+                    self.input.push(' ');
+                }
+            },
+            [first, rest @ ..] => {
+                let common = rest.iter().fold(first.len(), |len, name| {
+                    first.bytes().zip(name.bytes()).take(len).take_while(|(a, b)| a == b).count()
+                });
+                let prefix = first[..common].to_string();
+                self.log(LogLine::Output(candidates.join("  ")));
+                self.input.truncate(start);
+                self.input.push_str(&prefix);
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Command::parse("step"), Ok(Command::Step(1)));
+        assert_eq!(Command::parse(" step 10 "), Ok(Command::Step(10)));
+        assert_eq!(Command::parse("break Main if A == 1"), Ok(Command::Break("Main if A == 1")));
+        assert_eq!(Command::parse("set A = $12"), Ok(Command::Set { target: "A", value: "$12" }));
+        assert_eq!(
+            Command::parse("write $C000 $FF 1"),
+            Ok(Command::Write { location: "$C000", values: vec!["$FF", "1"] }),
+        );
+        assert_eq!(Command::parse("dump HL"), Ok(Command::Dump { location: "HL", length: None }));
+        assert_eq!(Command::parse("c"), Ok(Command::Continue));
+        assert_eq!(Command::parse("n"), Ok(Command::Next));
+        assert_eq!(Command::parse("rewind 3"), Ok(Command::Rewind(3)));
+        assert_eq!(Command::parse("rs"), Ok(Command::ReverseStep(1)));
+        assert_eq!(Command::parse("reverse-step 10"), Ok(Command::ReverseStep(10)));
+        assert_eq!(Command::parse("rc"), Ok(Command::ReverseContinue));
+        assert!(Command::parse("reverse-continue 2").is_err());
+        assert_eq!(Command::parse("press a start"), Ok(Command::Press("a start")));
+        assert_eq!(Command::parse("reset hard"), Ok(Command::Reset { hard: true }));
+        assert_eq!(Command::parse("until Main.loop"), Ok(Command::Until("Main.loop")));
+
+        assert!(Command::parse("step x").is_err());
+        assert!(Command::parse("set A").is_err());
+        assert!(Command::parse("write $C000").is_err());
+        assert!(Command::parse("continue 1").is_err());
+        assert!(Command::parse("break").is_err());
+        assert!(Command::parse("until").is_err());
+        assert!(Command::parse("reset soft").is_err());
+        assert!(Command::parse("finish now").is_err());
+        assert!(Command::parse("jump").is_err());
+
+        assert!(Command::parse("set A = 1").unwrap().edits());
+        assert!(Command::parse("press b").unwrap().edits());
+        assert!(!Command::parse("print A").unwrap().edits());
+        assert!(!Command::parse("step 5").unwrap().edits());
+    }
+
+    #[test]
+    fn test_history() {
+        let mut console = Console::new();
+        for line in ["step", "step", "continue"] {
+            console.input = line.to_string();
+            console.submit();
+        }
+        console.history_next();
+        assert_eq!(console.input, "");
+        console.history_prev();
+        assert_eq!(console.input, "continue");
+        console.history_prev();
+        console.history_prev();
+        assert_eq!(console.input, "step");
+        console.history_next();
+        assert_eq!(console.input, "continue");
+        console.history_next();
+        assert_eq!(console.input, "");
+    }
+
+    #[test]
+    fn test_complete() {
+        let mut console = Console::new();
+        console.input = "con".to_string();
+        console.complete(std::iter::empty());
+        assert_eq!(console.input, "continue ");
+
+        let names = ["UpdateSprites", "UpdateScore", "Main"];
+        console.input = "break Upd".to_string();
+        console.complete(names.iter().copied());
+        assert_eq!(console.input, "break UpdateS");
+        assert_eq!(console.lines(), &[LogLine::Output("UpdateScore  UpdateSprites".to_string())]);
+
+        console.input = "print [Ma".to_string();
+        console.complete(names.iter().copied());
+        assert_eq!(console.input, "print [Main");
+
+        console.input = "s".to_string();
+        console.complete(std::iter::empty());
+        assert_eq!(console.input, "s");
+    }
+}
@@ -0,0 +1,449 @@
+use std::fmt;
+
+use crate::{cpu::{CPU, Flag}, disasm::symbols::Symbols};
+
+/*
+    Debugger expressions
+
+    Operands:
+        A B C D E F H L AF BC DE HL SP PC   registers
+        Z N ZF NF HF CF                     flags, 1 if set
+        C H                                 registers, flags in breakpoint and
+                                            watchpoint conditions, where the
+                                            registers are read as BC & $FF
+                                            and HL >> 8
+        $FF 0xFF %1010 255                  numbers, decimal by default
+        UpdateSprites                       label address
+        [HL] [$C000]                        byte in memory
+
+    Operators from the lowest precedence:
+        ||  &&  |  ^  &  == !=  < <= > >=  << >>  + -  * / %  unary - ! ~
+*/
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExprError {
+    UnexpectedEnd,
+    UnexpectedToken(String),
+    InvalidNumber(String),
+    UnknownName(String),
+    DivisionByZero,
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExprError::UnexpectedEnd => write!(f, "unexpected end of expression"),
+            ExprError::UnexpectedToken(t) => write!(f, "unexpected {}", t),
+            ExprError::InvalidNumber(n) => write!(f, "invalid number {}", n),
+            ExprError::UnknownName(n) => write!(f, "unknown register or label {}", n),
+            ExprError::DivisionByZero => write!(f, "division by zero"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reg {
+    A, B, C, D, E, F, H, L,
+    AF, BC, DE, HL, SP, PC,
+}
+
+impl Reg {
+    pub fn parse(name: &str) -> Option<Reg> {
+        let reg = match name.to_uppercase().as_str() {
+            "A" => Reg::A, "B" => Reg::B, "C" => Reg::C, "D" => Reg::D,
+            "E" => Reg::E, "F" => Reg::F, "H" => Reg::H, "L" => Reg::L,
+            "AF" => Reg::AF, "BC" => Reg::BC, "DE" => Reg::DE, "HL" => Reg::HL,
+            "SP" => Reg::SP, "PC" => Reg::PC,
+            _ => return None,
+        };
+        Some(reg)
+    }
+
+    pub fn read(self, cpu: &CPU) -> u16 {
+        match self {
+            Reg::A => cpu.reg_af.hi() as u16,
+            Reg::F => cpu.reg_af.lo() as u16,
+            Reg::B => cpu.reg_bc.hi() as u16,
+            Reg::C => cpu.reg_bc.lo() as u16,
+            Reg::D => cpu.reg_de.hi() as u16,
+            Reg::E => cpu.reg_de.lo() as u16,
+            Reg::H => cpu.reg_hl.hi() as u16,
+            Reg::L => cpu.reg_hl.lo() as u16,
+            Reg::AF => cpu.reg_af.value,
+            Reg::BC => cpu.reg_bc.value,
+            Reg::DE => cpu.reg_de.value,
+            Reg::HL => cpu.reg_hl.value,
+            Reg::SP => cpu.stack_pointer.value,
+            Reg::PC => cpu.pc.value,
+        }
+    }
+
+    /// Writes `value` truncated to the register size, low bits of F are always 0
+    pub fn write(self, cpu: &mut CPU, value: u16) {
+        let byte = value as u8;
+        match self {
+            Reg::A => cpu.reg_af.write_hi(byte),
+            Reg::F => cpu.reg_af.write_lo(byte & 0xF0),
+            Reg::B => cpu.reg_bc.write_hi(byte),
+            Reg::C => cpu.reg_bc.write_lo(byte),
+            Reg::D => cpu.reg_de.write_hi(byte),
+            Reg::E => cpu.reg_de.write_lo(byte),
+            Reg::H => cpu.reg_hl.write_hi(byte),
+            Reg::L => cpu.reg_hl.write_lo(byte),
+            Reg::AF => cpu.reg_af.value = value & 0xFFF0,
+            Reg::BC => cpu.reg_bc.value = value,
+            Reg::DE => cpu.reg_de.value = value,
+            Reg::HL => cpu.reg_hl.value = value,
+            Reg::SP => cpu.stack_pointer.value = value,
+            Reg::PC => cpu.pc.value = value,
+        }
+    }
+}
+
+/// Flag named `name`, `C` and `H` are registers so those flags are `CF` and `HF`.
+/// Conditions take them as flags too, see `Expr::parse_condition`
+pub fn parse_flag(name: &str) -> Option<Flag> {
+    match name.to_uppercase().as_str() {
+        "Z" | "ZF" => Some(Flag::Z),
+        "N" | "NF" => Some(Flag::N),
+        "HF" => Some(Flag::H),
+        "CF" => Some(Flag::C),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+    BitNot,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or, And,
+    BitOr, BitXor, BitAnd,
+    Eq, Ne, Lt, Le, Gt, Ge,
+    Shl, Shr,
+    Add, Sub,
+    Mul, Div, Rem,
+}
+
+impl BinaryOp {
+    /// Operators grouped by precedence, from the lowest
+    const LEVELS: [&'static [(&'static str, BinaryOp)]; 10] = [
+        &[("||", BinaryOp::Or)],
+        &[("&&", BinaryOp::And)],
+        &[("|", BinaryOp::BitOr)],
+        &[("^", BinaryOp::BitXor)],
+        &[("&", BinaryOp::BitAnd)],
+        &[("==", BinaryOp::Eq), ("!=", BinaryOp::Ne)],
+        &[("<=", BinaryOp::Le), (">=", BinaryOp::Ge), ("<", BinaryOp::Lt), (">", BinaryOp::Gt)],
+        &[("<<", BinaryOp::Shl), (">>", BinaryOp::Shr)],
+        &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+        &[("*", BinaryOp::Mul), ("/", BinaryOp::Div), ("%", BinaryOp::Rem)],
+    ];
+}
+
+/// Parsed expression, label names are resolved when parsing
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Number(i64),
+    Register(Reg),
+    Flag(Flag),
+    /// Byte in memory
+    Deref(Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+/// Symbols the expression is built from
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Op(&'static str),
+}
+
+const OPERATORS: [&str; 23] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<<", ">>",
+    "|", "^", "&", "<", ">", "+", "-", "*", "/", "%", "!", "~", "(", ")", "[",
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, ExprError> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        if let Some(r) = rest.strip_prefix(']') {
+            tokens.push(Token::Op("]"));
+            rest = r;
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            // `%` followed by a binary digit is a number
+            if *op == "%" && rest[1..].starts_with(['0', '1'])
+                && !matches!(tokens.last(), Some(Token::Number(_)) | Some(Token::Name(_)) | Some(Token::Op(")")) | Some(Token::Op("]")))
+            {
+                let len = 1 + rest[1..].find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len() - 1);
+                tokens.push(Token::Number(parse_number(&rest[..len])?));
+                rest = &rest[len..];
+            } else {
+                tokens.push(Token::Op(op));
+                rest = &rest[op.len()..];
+            }
+        } else {
+            let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$';
+            let len = rest.find(|c: char| !is_word(c)).unwrap_or(rest.len());
+            if len == 0 {
+                return Err(ExprError::UnexpectedToken(rest.chars().next().unwrap_or_default().to_string()));
+            }
+            let word = &rest[..len];
+            if word.starts_with(|c: char| c.is_ascii_digit() || c == '$') {
+                tokens.push(Token::Number(parse_number(word)?));
+            } else {
+                tokens.push(Token::Name(word.to_string()));
+            }
+            rest = &rest[len..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+fn parse_number(text: &str) -> Result<i64, ExprError> {
+    let lower = text.to_lowercase();
+    let parsed = if let Some(hex) = lower.strip_prefix('$').or_else(|| lower.strip_prefix("0x")) {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(bin) = lower.strip_prefix('%') {
+        i64::from_str_radix(bin, 2)
+    } else {
+        lower.parse::<i64>()
+    };
+    parsed.map_err(|_| ExprError::InvalidNumber(text.to_string()))
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    symbols: &'a Symbols,
+    /// `C` and `H` are flags instead of registers
+    condition: bool,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token, ExprError> {
+        let token = self.tokens.get(self.pos).cloned().ok_or(ExprError::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, op: &'static str) -> Result<(), ExprError> {
+        match self.next()? {
+            Token::Op(o) if o == op => Ok(()),
+            t => Err(ExprError::UnexpectedToken(token_text(&t))),
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, ExprError> {
+        if level == BinaryOp::LEVELS.len() {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Op(o)) => BinaryOp::LEVELS[level].iter().find(|(s, _)| s == o).map(|(_, op)| *op),
+                _ => None,
+            };
+            match op {
+                Some(op) => {
+                    self.pos += 1;
+                    let right = self.binary(level + 1)?;
+                    left = Expr::Binary(op, Box::new(left), Box::new(right));
+                },
+                None => return Ok(left),
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ExprError> {
+        let op = match self.peek() {
+            Some(Token::Op("-")) => UnaryOp::Neg,
+            Some(Token::Op("!")) => UnaryOp::Not,
+            Some(Token::Op("~")) => UnaryOp::BitNot,
+            _ => return self.primary(),
+        };
+        self.pos += 1;
+        Ok(Expr::Unary(op, Box::new(self.unary()?)))
+    }
+
+    fn primary(&mut self) -> Result<Expr, ExprError> {
+        match self.next()? {
+            Token::Number(n) => Ok(Expr::Number(n)),
+            Token::Op("(") => {
+                let e = self.binary(0)?;
+                self.expect(")")?;
+                Ok(e)
+            },
+            Token::Op("[") => {
+                let e = self.binary(0)?;
+                self.expect("]")?;
+                Ok(Expr::Deref(Box::new(e)))
+            },
+            Token::Name(name) => {
+                if self.condition {
+                    match name.to_uppercase().as_str() {
+                        "C" => return Ok(Expr::Flag(Flag::C)),
+                        "H" => return Ok(Expr::Flag(Flag::H)),
+                        _ => {},
+                    }
+                }
+                if let Some(reg) = Reg::parse(&name) {
+                    return Ok(Expr::Register(reg));
+                }
+                if let Some(flag) = parse_flag(&name) {
+                    return Ok(Expr::Flag(flag));
+                }
+                match self.symbols.location(&name) {
+                    Some(location) => Ok(Expr::Number(location.addr as i64)),
+                    None => Err(ExprError::UnknownName(name)),
+                }
+            },
+            t => Err(ExprError::UnexpectedToken(token_text(&t))),
+        }
+    }
+}
+
+fn token_text(token: &Token) -> String {
+    match token {
+        Token::Number(n) => n.to_string(),
+        Token::Name(name) => name.clone(),
+        Token::Op(op) => op.to_string(),
+    }
+}
+
+impl Expr {
+    pub fn parse(text: &str, symbols: &Symbols) -> Result<Expr, ExprError> {
+        Expr::parse_with(text, symbols, false)
+    }
+
+    /// Parses breakpoint or watchpoint condition, where `C` and `H` are flags
+    /// like in `JP C` and `Z` is
+    pub fn parse_condition(text: &str, symbols: &Symbols) -> Result<Expr, ExprError> {
+        Expr::parse_with(text, symbols, true)
+    }
+
+    fn parse_with(text: &str, symbols: &Symbols, condition: bool) -> Result<Expr, ExprError> {
+        let mut parser = Parser { tokens: tokenize(text)?, pos: 0, symbols, condition };
+        let expr = parser.binary(0)?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(t) => Err(ExprError::UnexpectedToken(token_text(t))),
+        }
+    }
+
+    /// Evaluates expression with current state of `cpu`, memory is read without side effects
+    pub fn eval(&self, cpu: &CPU) -> Result<i64, ExprError> {
+        let v = match self {
+            Expr::Number(n) => *n,
+            Expr::Register(reg) => reg.read(cpu) as i64,
+            Expr::Flag(flag) => cpu.get_flag(*flag) as i64,
+            Expr::Deref(addr) => cpu.memory.peek(addr.eval(cpu)? as u16 as usize) as i64,
+            Expr::Unary(op, e) => {
+                let v = e.eval(cpu)?;
+                match op {
+                    UnaryOp::Neg => v.wrapping_neg(),
+                    UnaryOp::Not => (v == 0) as i64,
+                    UnaryOp::BitNot => !v,
+                }
+            },
+            Expr::Binary(BinaryOp::And, l, r) => (l.eval(cpu)? != 0 && r.eval(cpu)? != 0) as i64,
+            Expr::Binary(BinaryOp::Or, l, r) => (l.eval(cpu)? != 0 || r.eval(cpu)? != 0) as i64,
+            Expr::Binary(op, l, r) => {
+                let (l, r) = (l.eval(cpu)?, r.eval(cpu)?);
+                match op {
+                    BinaryOp::BitOr => l | r,
+                    BinaryOp::BitXor => l ^ r,
+                    BinaryOp::BitAnd => l & r,
+                    BinaryOp::Eq => (l == r) as i64,
+                    BinaryOp::Ne => (l != r) as i64,
+                    BinaryOp::Lt => (l < r) as i64,
+                    BinaryOp::Le => (l <= r) as i64,
+                    BinaryOp::Gt => (l > r) as i64,
+                    BinaryOp::Ge => (l >= r) as i64,
+                    BinaryOp::Shl => l.wrapping_shl(r as u32),
+                    BinaryOp::Shr => l.wrapping_shr(r as u32),
+                    BinaryOp::Add => l.wrapping_add(r),
+                    BinaryOp::Sub => l.wrapping_sub(r),
+                    BinaryOp::Mul => l.wrapping_mul(r),
+                    BinaryOp::Div | BinaryOp::Rem if r == 0 => return Err(ExprError::DivisionByZero),
+                    BinaryOp::Div => l.wrapping_div(r),
+                    BinaryOp::Rem => l.wrapping_rem(r),
+                    BinaryOp::And | BinaryOp::Or => unreachable!(),
+                }
+            },
+        };
+        Ok(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::Location;
+
+    fn eval(text: &str, cpu: &CPU) -> Result<i64, ExprError> {
+        let mut symbols = Symbols::new();
+        symbols.insert(Location::new(0, 0xC000), "wCounter");
+        Expr::parse(text, &symbols)?.eval(cpu)
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let cpu = CPU::new();
+        assert_eq!(eval("1 + 2 * 3", &cpu), Ok(7));
+        assert_eq!(eval("(1 + 2) * 3", &cpu), Ok(9));
+        assert_eq!(eval("$10 | %0101 << 1", &cpu), Ok(0x1A));
+        assert_eq!(eval("1 << 2 + 1", &cpu), Ok(8));
+        assert_eq!(eval("$10 >> 1 - 1", &cpu), Ok(0x10));
+        assert_eq!(eval("10 % 4 - -1", &cpu), Ok(3));
+        assert_eq!(eval("1 < 2 && 2 >= 2 || 0", &cpu), Ok(1));
+        assert_eq!(eval("!0 + ~0", &cpu), Ok(0));
+        assert_eq!(eval("1 / 0", &cpu), Err(ExprError::DivisionByZero));
+    }
+
+    #[test]
+    fn test_cpu_state() {
+        let mut cpu = CPU::new();
+        cpu.reg_af.value = 0x12B0;
+        cpu.reg_hl.value = 0xC000;
+        cpu.memory[0xC000u16] = 0x42;
+        assert_eq!(eval("A == $12 && HL == wCounter", &cpu), Ok(1));
+        assert_eq!(eval("[HL] + [wCounter + 1]", &cpu), Ok(0x42));
+        assert_eq!(eval("Z + N * 2 + HF * 4 + CF * 8", &cpu), Ok(0b1101));
+        assert_eq!(eval("c", &cpu), Ok(0x13));
+        assert_eq!(eval("SP", &cpu), Ok(0xFFFE));
+
+        let condition = |text| Expr::parse_condition(text, &Symbols::new()).unwrap().eval(&cpu);
+        assert_eq!(condition("C"), Ok(1));
+        assert_eq!(condition("h + z * 2"), Ok(3));
+        assert_eq!(condition("BC & $FF"), Ok(0x13));
+
+        Reg::AF.write(&mut cpu, 0x34FF);
+        Reg::L.write(&mut cpu, 0x1FF);
+        assert_eq!(eval("AF == $34F0 && HL == $C0FF", &cpu), Ok(1));
+    }
+
+    #[test]
+    fn test_errors() {
+        let cpu = CPU::new();
+        assert_eq!(eval("1 +", &cpu), Err(ExprError::UnexpectedEnd));
+        assert_eq!(eval("[HL", &cpu), Err(ExprError::UnexpectedEnd));
+        assert_eq!(eval("1 2", &cpu), Err(ExprError::UnexpectedToken("2".to_string())));
+        assert_eq!(eval("Missing", &cpu), Err(ExprError::UnknownName("Missing".to_string())));
+        assert_eq!(eval("$XY", &cpu), Err(ExprError::InvalidNumber("$XY".to_string())));
+        assert_eq!(eval("é", &cpu), Err(ExprError::UnexpectedToken("é".to_string())));
+        assert_eq!(eval("A == é", &cpu), Err(ExprError::UnexpectedToken("é".to_string())));
+    }
+}
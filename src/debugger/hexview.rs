@@ -0,0 +1,137 @@
+use crate::core::memory::Memory;
+
+pub const BYTES_PER_ROW: u16 = 16;
+
+/// Bytes moved by PageUp/PageDown
+pub const PAGE_SIZE: u16 = 0x100;
+
+/// Memory regions reachable by shortcut keys 1-7
+pub const REGIONS: [(&str, u16); 7] = [
+    ("ROM0", 0x0000),
+    ("ROMX", 0x4000),
+    ("VRAM", 0x8000),
+    ("WRAM", 0xC000),
+    ("OAM", 0xFE00),
+    ("IO", 0xFF00),
+    ("HRAM", 0xFF80),
+];
+
+/// ## Hex memory view
+/// Selected byte, in-place editing and bytes changed by the last step.
+/// Memory is only read with `peek`, so inspecting I/O registers changes nothing
+#[derive(Clone, Debug)]
+pub struct HexView {
+    /// Selected byte
+    pub cursor: u16,
+    /// Typed digits go to the selected byte
+    pub editing: bool,
+    /// High nibble typed while editing
+    pending: Option<u8>,
+    /// Memory before the last step
+    previous: Vec<u8>,
+}
+
+impl HexView {
+    pub fn new(memory: &Memory) -> HexView {
+        HexView { cursor: 0, editing: false, pending: None, previous: memory.peek_all().to_vec() }
+    }
+
+    /// Remembers memory before a step, so changed bytes can be highlighted
+    pub fn snapshot(&mut self, memory: &Memory) {
+        self.previous.copy_from_slice(memory.peek_all());
+    }
+
+    pub fn is_changed(&self, memory: &Memory, addr: u16) -> bool {
+        self.previous[addr as usize] != memory.peek(addr as usize)
+    }
+
+    /// Moves selection by `delta` bytes, stopping at the ends of the address space
+    pub fn move_by(&mut self, delta: i32) {
+        self.cursor = (self.cursor as i32 + delta).clamp(0, 0xFFFF) as u16;
+        self.pending = None;
+    }
+
+    pub fn goto(&mut self, addr: u16) {
+        self.cursor = addr;
+        self.pending = None;
+    }
+
+    /// High nibble typed so far
+    pub fn pending(&self) -> Option<u8> {
+        self.pending
+    }
+
+    pub fn stop_editing(&mut self) {
+        self.editing = false;
+        self.pending = None;
+    }
+
+    /// Types hex digit into the selected byte, the byte is written once both
+    /// nibbles are typed and selection moves to the next one
+    pub fn input(&mut self, digit: char, memory: &mut Memory) -> bool {
+        let digit = match digit.to_digit(16) {
+            Some(d) => d as u8,
+            None => return false,
+        };
+        match self.pending.take() {
+            None => self.pending = Some(digit),
+            Some(high) => {
+                memory[self.cursor] = high << 4 | digit;
+                self.move_by(1);
+            },
+        }
+        true
+    }
+
+    /// First address of the rows shown when `rows` fit on screen, keeps the
+    /// selected row in the middle
+    pub fn first_row(&self, rows: u16) -> u16 {
+        let total = 0x10000 / BYTES_PER_ROW as u32;
+        let selected = (self.cursor / BYTES_PER_ROW) as u32;
+        let first = selected.saturating_sub(rows as u32 / 2).min(total.saturating_sub(rows as u32));
+        (first * BYTES_PER_ROW as u32) as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit() {
+        let mut mem = Memory::new();
+        let mut view = HexView::new(&mem);
+        view.goto(0xC000);
+        view.editing = true;
+        assert!(view.input('1', &mut mem));
+        assert!(!view.input('x', &mut mem));
+        assert_eq!(view.pending(), Some(1));
+        assert!(view.input('f', &mut mem));
+        assert!(view.input('A', &mut mem));
+        view.move_by(-1);
+        assert_eq!(view.pending(), None);
+        assert_eq!(mem.peek(0xC000), 0x1F);
+        assert_eq!(view.cursor, 0xC000);
+
+        assert!(view.is_changed(&mem, 0xC000));
+        assert!(!view.is_changed(&mem, 0xC001));
+        view.snapshot(&mem);
+        assert!(!view.is_changed(&mem, 0xC000));
+    }
+
+    #[test]
+    fn test_scroll() {
+        let mem = Memory::new();
+        let mut view = HexView::new(&mem);
+        view.move_by(-(PAGE_SIZE as i32));
+        assert_eq!(view.cursor, 0);
+        assert_eq!(view.first_row(10), 0);
+
+        view.goto(0xC085);
+        assert_eq!(view.first_row(10), 0xC030);
+
+        view.move_by(0x10000);
+        assert_eq!(view.cursor, 0xFFFF);
+        assert_eq!(view.first_row(10), 0xFF60);
+    }
+}
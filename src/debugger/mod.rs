@@ -1,13 +1,77 @@
-use std::{sync::mpsc, thread, time::{Duration, Instant}, io};
+use std::{collections::HashMap, panic, sync::mpsc::{self, TryRecvError}, thread, time::{Duration, Instant}, io, fs, path::{Path, PathBuf}};
 use crossterm::{terminal::{enable_raw_mode, disable_raw_mode}, event::{self, Event as CEvent, KeyCode}};
-use tui::{backend::CrosstermBackend, Terminal, layout::{Layout, Direction, Constraint, Alignment}, widgets::{Paragraph, Block, Borders, BorderType, Tabs, ListState}, style::{Style, Color, Modifier}, text::{Spans, Span}};
+use tui::{backend::CrosstermBackend, Terminal, layout::{Layout, Direction, Constraint}, widgets::{Block, Borders, Tabs, ListState}, style::{Style, Color, Modifier}, text::{Spans, Span}};
 
-use crate::cpu::CPU;
+use crate::{cpu::CPU, core::{assembler::Assembler, bess, memory::BUTTONS, state::MachineState, trace::{Trace, TraceLine}, watchpoints::Watchpoint}, disasm::{Disassembler, Location, rgbds, symbols::Symbols}};
+
+use self::{
+    breakpoints::{Breakpoints, Condition, parse_location, parse_range, parse_watchpoint, split_condition},
+    compare::{Comparison, CONTEXT_LINES},
+    console::{Command, Console, LogLine, COMMANDS},
+    expr::{Expr, Reg, parse_flag},
+    hexview::{HexView, BYTES_PER_ROW, PAGE_SIZE, REGIONS},
+    oam::SPRITE_COUNT,
+    rewind::{Rewind, REWIND_CAPACITY, REWIND_INTERVAL},
+    stepping::RunMode,
+    tilemap::MapView,
+    tiles::{TileView, TILES_PER_ROW, tile_count},
+};
 
 mod ui;
+mod breakpoints;
+mod compare;
+mod console;
+mod expr;
+mod hexview;
+mod tiles;
+mod tilemap;
+mod oam;
+mod rewind;
+mod screenshot;
+mod stepping;
+
+/// Directory RGBDS sources are exported to
+const ASM_EXPORT_DIR: &str = "disasm";
+
+/// Cycles in a single frame, amount of emulation done between redraws while running
+pub const FRAME_CYCLES: u32 = 70224;
+
+/// Height of the console panel, including borders
+const CONSOLE_HEIGHT: u16 = 10;
+
+/// Save state slots, stored next to the ROM as `.ss0`-`.ss9`
+const SAVE_SLOTS: u8 = 10;
+
+/// Bytes printed by `dump` when no length is given
+const DUMP_LENGTH: u16 = 64;
+
+/// Register names offered by console completion
+const REGISTER_NAMES: [&str; 14] = ["A", "B", "C", "D", "E", "F", "H", "L", "AF", "BC", "DE", "HL", "SP", "PC"];
 
 pub struct Debugger {
-    cpu: CPU
+    cpu: CPU,
+    /// File the cartridge was loaded from, reloaded by hard reset
+    rom_path: Option<PathBuf>,
+    symbols: Symbols,
+    breakpoints: Breakpoints,
+    watch_conditions: HashMap<Watchpoint, Condition>,
+    assembler: Assembler,
+    console: Console,
+    /// Emulation runs continuously until a breakpoint is hit or the mode is done
+    run: Option<RunMode>,
+    /// Selected line of the assembly pane, follows PC when not set
+    asm_cursor: Option<u16>,
+    /// Why execution stopped last time
+    status: Option<String>,
+    tab: MenuItem,
+    hexview: HexView,
+    tiles: TileView,
+    tilemap: MapView,
+    /// Sprite selected in the OAM tab
+    sprite: usize,
+    rewind: Rewind,
+    /// State was replaced since the assembly view was disassembled
+    replaced: bool,
 }
 
 enum Event<I> {
@@ -17,30 +81,731 @@ enum Event<I> {
 
 #[derive(Copy, Clone, Debug)]
 enum MenuItem {
-    CPU,
-    VRAM,
-    Memory
+    Cpu,
+    Vram,
+    Memory,
+    Tilemap,
+    Oam,
 }
 
 impl From<MenuItem> for usize {
     fn from(input: MenuItem) -> usize {
         match input {
-            MenuItem::CPU => 0,
-            MenuItem::VRAM => 1,
+            MenuItem::Cpu => 0,
+            MenuItem::Vram => 1,
             MenuItem::Memory => 2,
+            MenuItem::Tilemap => 3,
+            MenuItem::Oam => 4,
         }
     }
 }
 
 impl Debugger {
     pub fn new(cpu: CPU) -> Debugger {
+        let mut rewind = Rewind::new(REWIND_INTERVAL, REWIND_CAPACITY);
+        rewind.clear(&cpu);
         Debugger{
-            cpu
+            hexview: HexView::new(&cpu.memory),
+            cpu,
+            rom_path: None,
+            symbols: Symbols::new(),
+            breakpoints: Breakpoints::new(),
+            watch_conditions: HashMap::new(),
+            assembler: Assembler::new(),
+            console: Console::new(),
+            run: None,
+            asm_cursor: None,
+            status: None,
+            tab: MenuItem::Cpu,
+            tiles: TileView::new(),
+            tilemap: MapView::new(),
+            sprite: 0,
+            rewind,
+            replaced: false,
+        }
+    }
+
+    /// Runs console commands from `path` line by line, lines starting with `#` are
+    /// comments. A missing file is ignored
+    pub fn run_script(&mut self, path: &Path) -> io::Result<()> {
+        let script = match fs::read_to_string(path) {
+            Ok(script) => script,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        for line in script.lines().map(str::trim) {
+            if !line.is_empty() && !line.starts_with('#') {
+                self.execute(line);
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs console command, its output goes to the console log
+    pub fn execute(&mut self, line: &str) {
+        self.console.log(LogLine::Input(line.to_string()));
+        let edits = Command::parse(line).is_ok_and(|c| c.edits());
+        match self.command(line) {
+            Ok(output) => {
+                output.into_iter().for_each(|l| self.console.log(LogLine::Output(l)));
+                // edited state has to survive rewinding
+                if edits {
+                    self.rewind.mark(&self.cpu);
+                }
+            },
+            Err(e) => self.console.log(LogLine::Error(e)),
+        }
+    }
+
+    fn command(&mut self, line: &str) -> Result<Vec<String>, String> {
+        let output = match Command::parse(line)? {
+            Command::Break(args) => vec![self.set_breakpoint(args)?],
+            Command::Watch(args) => vec![self.toggle_watchpoint(args)?],
+            Command::Step(n) => {
+                self.asm_cursor = None;
+                self.status = self.step_n(n);
+                vec![self.status.clone().unwrap_or_else(|| format!("PC = ${:04X}", self.cpu.pc.value))]
+            },
+            Command::Continue => {
+                self.resume(RunMode::Continue);
+                vec![]
+            },
+            Command::Next => {
+                self.step_over();
+                vec![]
+            },
+            Command::Finish => {
+                self.resume(RunMode::step_out(&self.cpu));
+                vec![]
+            },
+            Command::Until(location) => {
+                let location = self.location(location)?;
+                self.resume(RunMode::ToLocation(location));
+                vec![]
+            },
+            Command::Rewind(n) => {
+                let frame = self.rewind.frame().saturating_sub(n as u64);
+                self.rewind.seek_frame(&mut self.cpu, frame)?;
+                self.stopped(Some(format!("Rewound to frame {}", self.rewind.frame())));
+                vec![self.status.clone().unwrap_or_default()]
+            },
+            Command::ReverseStep(n) => {
+                let step = self.rewind.step.saturating_sub(n as u64).max(self.rewind.first_step());
+                self.rewind.seek(&mut self.cpu, step)?;
+                self.stopped(Some(format!(
+                    "Reversed to step {} at {}",
+                    step,
+                    Location::mapped(&self.cpu.memory, self.cpu.pc.value),
+                )));
+                vec![self.status.clone().unwrap_or_default()]
+            },
+            Command::Press(args) => {
+                let mut buttons = 0;
+                for name in args.split_whitespace() {
+                    let bit = BUTTONS
+                        .iter()
+                        .position(|b| b.eq_ignore_ascii_case(name))
+                        .ok_or_else(|| format!("unknown button {}", name))?;
+                    buttons |= 1 << bit;
+                }
+                self.rewind.input(&mut self.cpu, buttons);
+                match buttons {
+                    0 => vec!["Buttons released".to_string()],
+                    _ => vec![format!("Holding {}", args.split_whitespace().collect::<Vec<_>>().join(" "))],
+                }
+            },
+            Command::ReverseContinue => {
+                let status = self.reverse_continue()?;
+                self.stopped(Some(status));
+                vec![self.status.clone().unwrap_or_default()]
+            },
+            Command::Goto(location) => {
+                let addr = self.location(location)?.addr;
+                match self.tab {
+                    MenuItem::Memory => self.hexview.goto(addr),
+                    _ => self.asm_cursor = Some(addr),
+                }
+                vec![]
+            },
+            Command::Set { target, value } => {
+                let value = self.eval(value)?;
+                if let Some(reg) = Reg::parse(target) {
+                    reg.write(&mut self.cpu, value as u16);
+                    vec![self.print(target)?]
+                } else if let Some(flag) = parse_flag(target) {
+                    let f = self.cpu.reg_af.lo();
+                    let f = if value != 0 { f | flag.mask() } else { f & !flag.mask() };
+                    self.cpu.reg_af.write_lo(f);
+                    vec![self.print(target)?]
+                } else {
+                    return Err(format!("unknown register or flag {}", target));
+                }
+            },
+            Command::Write { location, values } => {
+                let addr = self.location(location)?.addr;
+                let bytes = values
+                    .iter()
+                    .map(|v| match self.eval(v)? {
+                        b @ -0x80..=0xFF => Ok(b as u8),
+                        b => Err(format!("{} does not fit in a byte", b)),
+                    })
+                    .collect::<Result<Vec<u8>, String>>()?;
+                for (i, b) in bytes.iter().enumerate() {
+                    self.cpu.memory[addr.wrapping_add(i as u16)] = *b;
+                }
+                vec![format!("Wrote {} bytes at ${:04X}", bytes.len(), addr)]
+            },
+            Command::Dump { location, length } => {
+                let addr = self.location(location)?.addr;
+                let length = match length {
+                    Some(length) => self.eval(length)?.clamp(0, 0x10000) as u32,
+                    None => DUMP_LENGTH as u32,
+                };
+                (0..length)
+                    .step_by(16)
+                    .map(|offset| {
+                        let row: Vec<u8> = (offset..(offset + 16).min(length))
+                            .map(|i| self.cpu.memory.peek(addr.wrapping_add(i as u16) as usize))
+                            .collect();
+                        let hex: Vec<String> = row.iter().map(|b| format!("{:02X}", b)).collect();
+                        let ascii: String = row
+                            .iter()
+                            .map(|b| if b.is_ascii_graphic() { *b as char } else { '.' })
+                            .collect();
+                        format!("${:04X}  {:<47}  {}", addr.wrapping_add(offset as u16), hex.join(" "), ascii)
+                    })
+                    .collect()
+            },
+            Command::Print(expr) => vec![self.print(expr)?],
+            Command::Patch(instruction) => {
+                let pc = self.cpu.pc.value;
+                let bytes = self.assembler
+                    .assemble(&self.symbols.expand(instruction), pc)
+                    .map_err(|e| e.to_string())?;
+                for (i, b) in bytes.iter().enumerate() {
+                    self.cpu.memory[pc.wrapping_add(i as u16)] = *b;
+                }
+                vec![]
+            },
+            Command::Trace(args) => vec![self.trace(args)?],
+            Command::Compare(args) => self.compare(args)?.1,
+            Command::Reset { hard } => vec![self.reset(hard)?],
+            Command::LoadState(target) => {
+                let path = self.state_path(target)?;
+                let data = fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                let mut output = vec![format!("Loaded state from {}", path.display())];
+                let state = if bess::is_bess(&data) {
+                    let import = bess::import(&data, self.cpu.memory.rom()).map_err(|e| e.to_string())?;
+                    output.push(format!(
+                        "BESS state of {} by {}",
+                        import.model,
+                        import.emulator.as_deref().unwrap_or("unknown emulator"),
+                    ));
+                    if !import.ignored.is_empty() {
+                        output.push(format!("Ignored blocks: {}", import.ignored.join(", ")));
+                    }
+                    import.state
+                } else {
+                    MachineState::decode(&data).map_err(|e| e.to_string())?
+                };
+                self.cpu.load_state(&state).map_err(|e| e.to_string())?;
+                self.rewind.clear(&self.cpu);
+                self.stopped(None);
+                output
+            },
+            Command::SaveState(args) => {
+                let (target, format) = match args.rsplit_once(char::is_whitespace) {
+                    Some((target, "bess")) => (target.trim(), "BESS "),
+                    _ if args == "bess" => ("", "BESS "),
+                    _ => (args, ""),
+                };
+                let path = self.state_path(target)?;
+                let state = self.cpu.save_state();
+                let saved = match format {
+                    "" => state.save(&path),
+                    _ => bess::save(&state, &path),
+                };
+                saved.map_err(|e| format!("{}: {}", path.display(), e))?;
+                vec![format!("Saved {}state to {}", format, path.display())]
+            },
+            Command::Export => {
+                let rom = self.cpu.memory.rom();
+                let listings = Disassembler::new().disassemble_rom(rom, &[]);
+                let files = rgbds::export(rom, &listings, Path::new(ASM_EXPORT_DIR)).map_err(|e| e.to_string())?;
+                vec![format!("Exported {} files to {}", files.len(), ASM_EXPORT_DIR)]
+            },
+            Command::Help => COMMANDS.iter().map(|(name, help)| format!("{} {}", name, help)).collect(),
+        };
+        Ok(output)
+    }
+
+    /// Starts continuous emulation until `mode` is done
+    fn resume(&mut self, mode: RunMode) {
+        self.hexview.snapshot(&self.cpu.memory);
+        self.run = Some(mode);
+        self.asm_cursor = None;
+        self.status = None;
+    }
+
+    /// Runs over the call at PC, other instructions are single stepped
+    fn step_over(&mut self) {
+        match RunMode::step_over(&self.cpu) {
+            Some(mode) => self.resume(mode),
+            None => {
+                self.asm_cursor = None;
+                self.status = self.step_n(1);
+            },
+        }
+    }
+
+    /// Emulates about a frame worth of cycles, stops when the run is done or
+    /// a breakpoint is hit
+    fn run_frame(&mut self) {
+        let mode = match self.run {
+            Some(mode) => mode,
+            None => return,
+        };
+        let mut cycles = 0;
+        while cycles < FRAME_CYCLES {
+            cycles += self.step();
+            if let Some(message) = self.stop_reason() {
+                self.run = None;
+                self.status = Some(message);
+                return;
+            }
+            if mode.is_done(&self.cpu) {
+                self.run = None;
+                self.status = Some(format!("Stopped at {}", Location::mapped(&self.cpu.memory, self.cpu.pc.value)));
+                return;
+            }
         }
     }
 
+    /// Executes one instruction, keeping track of it for rewinding
+    fn step(&mut self) -> u32 {
+        let cycles = self.cpu.step();
+        self.rewind.record(&self.cpu, cycles);
+        cycles
+    }
+
+    /// Stops emulation after state was replaced, with `status` as the reason
+    fn stopped(&mut self, status: Option<String>) {
+        self.replaced = true;
+        self.run = None;
+        self.asm_cursor = None;
+        self.status = status;
+        self.hexview.snapshot(&self.cpu.memory);
+    }
+
+    /// Goes back one step, or one frame
+    fn rewind_key(&mut self, frame: bool) {
+        let result = if frame {
+            let frame = self.rewind.frame().saturating_sub(1);
+            self.rewind.seek_frame(&mut self.cpu, frame)
+        } else {
+            let step = self.rewind.step.saturating_sub(1);
+            self.rewind.seek(&mut self.cpu, step)
+        };
+        let status = match result {
+            Ok(()) => format!(
+                "Rewound to frame {}, step {}, {} KiB of history",
+                self.rewind.frame(),
+                self.rewind.step,
+                self.rewind.size() / 1024,
+            ),
+            Err(e) => e,
+        };
+        self.stopped(Some(status));
+    }
+
+    /// Goes back to the last breakpoint or watchpoint hit before the current step,
+    /// or to the start of the rewind buffer when there is none
+    fn reverse_continue(&mut self) -> Result<String, String> {
+        let end = self.rewind.step;
+        // the search replays history on the CPU and counts condition hits again,
+        // both are put back afterwards
+        let rewind = std::mem::replace(&mut self.rewind, Rewind::new(REWIND_INTERVAL, 1));
+        let (breakpoints, watch_conditions) = (self.breakpoints.clone(), self.watch_conditions.clone());
+        let trace = self.cpu.trace.take();
+        let found = self.last_hit(&rewind, end);
+        self.rewind = rewind;
+        self.breakpoints = breakpoints;
+        self.watch_conditions = watch_conditions;
+        self.cpu.trace = trace;
+
+        let (step, status) = match found {
+            Ok(Some((step, reason))) => (step, format!("{} (step {})", reason, step)),
+            Ok(None) => (self.rewind.first_step(), "Reached the start of rewind history".to_string()),
+            Err(e) => {
+                self.rewind.seek(&mut self.cpu, end)?;
+                return Err(e);
+            },
+        };
+        self.rewind.seek(&mut self.cpu, step)?;
+        Ok(status)
+    }
+
+    /// Replays snapshots of `rewind` from the newest one, finds the last step
+    /// before `end` that would stop execution, with the reason
+    fn last_hit(&mut self, rewind: &Rewind, mut end: u64) -> Result<Option<(u64, String)>, String> {
+        for (start, state) in rewind.history() {
+            if start >= end {
+                continue;
+            }
+            let state = MachineState::decode(&state).map_err(|e| e.to_string())?;
+            self.cpu.load_state(&state).map_err(|e| e.to_string())?;
+            self.cpu.memory.watchpoints.take_hits();
+            let mut found = None;
+            for step in start + 1..end {
+                if let Some(buttons) = rewind.input_at(step - 1) {
+                    self.cpu.memory.set_buttons(buttons);
+                }
+                self.cpu.step();
+                if let Some(reason) = self.stop_reason() {
+                    found = Some((step, reason));
+                }
+            }
+            if found.is_some() {
+                return Ok(found);
+            }
+            // the snapshot step itself is checked by replaying the older one
+            end = start + 1;
+        }
+        Ok(None)
+    }
+
+    /// Executes up to `n` instructions, stopping early at breakpoints and watchpoints
+    fn step_n(&mut self, n: u32) -> Option<String> {
+        self.hexview.snapshot(&self.cpu.memory);
+        for _ in 0..n {
+            self.step();
+            if let Some(reason) = self.stop_reason() {
+                return Some(reason);
+            }
+        }
+        None
+    }
+
+    /// Handles keys of the memory tab, returns false for keys it doesn't use
+    fn memory_key(&mut self, key: KeyCode) -> bool {
+        let view = &mut self.hexview;
+        match key {
+            KeyCode::Up => view.move_by(-(BYTES_PER_ROW as i32)),
+            KeyCode::Down => view.move_by(BYTES_PER_ROW as i32),
+            KeyCode::Left => view.move_by(-1),
+            KeyCode::Right => view.move_by(1),
+            KeyCode::PageUp => view.move_by(-(PAGE_SIZE as i32)),
+            KeyCode::PageDown => view.move_by(PAGE_SIZE as i32),
+            KeyCode::Enter | KeyCode::Esc if view.editing => view.stop_editing(),
+            KeyCode::Char(c) if view.editing => {
+                if view.input(c, &mut self.cpu.memory) {
+                    self.rewind.mark(&self.cpu);
+                }
+            },
+            // other keys are ignored while editing
+            _ if view.editing => {},
+            KeyCode::Enter => view.editing = true,
+            KeyCode::Char(c @ '1'..='7') => view.goto(REGIONS[c as usize - '1' as usize].1),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Handles keys of the VRAM tab, returns false for keys it doesn't use
+    fn vram_key(&mut self, key: KeyCode) -> bool {
+        let count = tile_count(&self.cpu.memory);
+        match key {
+            KeyCode::Up => self.tiles.move_by(-(TILES_PER_ROW as i32), count),
+            KeyCode::Down => self.tiles.move_by(TILES_PER_ROW as i32, count),
+            KeyCode::Left => self.tiles.move_by(-1, count),
+            KeyCode::Right => self.tiles.move_by(1, count),
+            KeyCode::Tab => self.tiles.palette = self.tiles.palette.next(),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Handles keys of the tilemap tab, returns false for keys it doesn't use
+    fn tilemap_key(&mut self, key: KeyCode) -> bool {
+        match key {
+            KeyCode::Up => self.tilemap.scroll(0, -8),
+            KeyCode::Down => self.tilemap.scroll(0, 8),
+            KeyCode::Left => self.tilemap.scroll(-8, 0),
+            KeyCode::Right => self.tilemap.scroll(8, 0),
+            KeyCode::Enter => self.tilemap.center(&self.cpu.memory),
+            KeyCode::Tab => self.tilemap.next_map(),
+            KeyCode::Char('A') => self.tilemap.attributes = !self.tilemap.attributes,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Handles keys of the OAM tab, returns false for keys it doesn't use
+    fn oam_key(&mut self, key: KeyCode) -> bool {
+        match key {
+            KeyCode::Up => self.sprite = self.sprite.saturating_sub(1),
+            KeyCode::Down => self.sprite = (self.sprite + 1).min(SPRITE_COUNT - 1),
+            _ => return false,
+        }
+        true
+    }
+
+    fn location(&self, text: &str) -> Result<Location, String> {
+        parse_location(text, &self.symbols, &self.cpu.memory).ok_or_else(|| format!("unknown location {}", text))
+    }
+
+    fn eval(&self, text: &str) -> Result<i64, String> {
+        Expr::parse(text, &self.symbols)
+            .and_then(|e| e.eval(&self.cpu))
+            .map_err(|e| e.to_string())
+    }
+
+    /// Starts trace entered as `<file> [<start>-<end>] [limit N]`, stops it with `off`,
+    /// or describes the running one
+    pub fn trace(&mut self, text: &str) -> Result<String, String> {
+        let mut parts = text.split_whitespace();
+        let path = match parts.next() {
+            None => return Ok(match &self.cpu.trace {
+                Some(trace) => format!(
+                    "Tracing ${:04X}-${:04X}, {} lines written{}",
+                    trace.range.start(),
+                    trace.range.end(),
+                    trace.lines(),
+                    trace.error.as_ref().map(|e| format!(", stopped: {}", e)).unwrap_or_default(),
+                ),
+                None => "Trace is off".to_string(),
+            }),
+            Some("off") => {
+                let mut trace = self.cpu.trace.take().ok_or("trace is not running")?;
+                trace.flush();
+                return match &trace.error {
+                    Some(e) => Err(format!("trace failed after {} lines: {}", trace.lines(), e)),
+                    None => Ok(format!("Trace stopped, {} lines written", trace.lines())),
+                };
+            },
+            Some(path) => path,
+        };
+        let mut range = 0x0000..=0xFFFF;
+        let mut limit = None;
+        while let Some(part) = parts.next() {
+            match part {
+                "limit" => {
+                    let n = parts.next().ok_or("expected line count after limit")?;
+                    limit = Some(n.parse().map_err(|_| format!("invalid line count {}", n))?);
+                },
+                _ => {
+                    range = parse_range(part, &self.symbols, &self.cpu.memory)
+                        .ok_or_else(|| format!("invalid address range {}", part))?;
+                },
+            }
+        }
+        let mut trace = Trace::create(Path::new(path)).map_err(|e| format!("{}: {}", path, e))?;
+        trace.range = range;
+        trace.limit = limit;
+        self.cpu.trace = Some(trace);
+        Ok(format!("Tracing to {}", path))
+    }
+
+    /// File of save state slot `0`-`9` next to the ROM, or any other path.
+    /// Slot 0 is used when `target` is empty
+    fn state_path(&self, target: &str) -> Result<PathBuf, String> {
+        let slot = match target {
+            "" => 0,
+            _ => match target.parse::<u8>() {
+                Ok(slot) if slot < SAVE_SLOTS => slot,
+                Ok(slot) => return Err(format!("invalid slot {}, there are {}", slot, SAVE_SLOTS)),
+                Err(_) => return Ok(PathBuf::from(target)),
+            },
+        };
+        let rom = self.rom_path.as_ref().ok_or("no ROM file to put slots next to")?;
+        Ok(rom.with_extension(format!("ss{}", slot)))
+    }
+
+    /// Runs until CPU state differs from trace entered as `<file> [context N]`,
+    /// describes the divergence. The flag is set when the whole trace matched
+    pub fn compare(&mut self, text: &str) -> Result<(bool, Vec<String>), String> {
+        let mut parts = text.split_whitespace();
+        let path = parts.next().ok_or("expected trace file")?;
+        let context = match (parts.next(), parts.next()) {
+            (None, _) => CONTEXT_LINES,
+            (Some("context"), Some(n)) => n.parse().map_err(|_| format!("invalid line count {}", n))?,
+            _ => return Err("expected context N".to_string()),
+        };
+        let file = fs::File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        self.stopped(None);
+        let comparison = compare::compare(&mut self.cpu, io::BufReader::new(file), context);
+        // comparison steps the CPU on its own
+        self.rewind.clear(&self.cpu);
+        let divergence = match comparison? {
+            Comparison::Matched(lines) => {
+                let message = format!("Trace matched, {} instructions", lines);
+                self.status = Some(message.clone());
+                return Ok((true, vec![message]));
+            },
+            Comparison::Diverged(divergence) => divergence,
+        };
+
+        self.status = Some(format!("Trace diverged at line {}", divergence.line));
+        let mut output = vec![format!("Diverged at line {} of {}, last matching instructions:", divergence.line, path)];
+        output.extend(divergence.history.iter().map(|line| format!("  {}  {}", self.disassemble(line), line)));
+        output.push(format!("expected {}", divergence.expected));
+        output.push(format!("actual   {}", divergence.actual));
+        output.extend(divergence.expected.differences(&divergence.actual));
+        output.push(format!(
+            "at {}: {}",
+            ui::describe(&self.symbols, &self.cpu.memory, divergence.actual.pc),
+            self.disassemble(&divergence.actual),
+        ));
+        if divergence.expected.pcmem != divergence.actual.pcmem {
+            output.push(format!("expected instruction: {}", self.disassemble(&divergence.expected)));
+        }
+        Ok((false, output))
+    }
+
+    /// Instruction in PCMEM of a trace line
+    fn disassemble(&self, line: &TraceLine) -> String {
+        let location = Location::mapped(&self.cpu.memory, line.pc);
+        match Disassembler::new().decode_bytes(location, &line.pcmem) {
+            Some(asm) => format!("${:04X} {:<14}", line.pc, asm.text_with(|addr| self.symbols.name(Location::mapped(&self.cpu.memory, addr)))),
+            None => format!("${:04X} {:<14}", line.pc, "?"),
+        }
+    }
+
+    /// Loads cartridge and restarts emulation
+    pub fn load_rom(&mut self, path: &Path) -> io::Result<()> {
+        self.cpu.memory.load(path)?;
+        self.rom_path = Some(path.to_path_buf());
+        self.cpu.reset();
+        self.rewind.clear(&self.cpu);
+        Ok(())
+    }
+
+    /// Maps boot ROM over the cartridge and restarts emulation from it
+    pub fn load_boot_rom(&mut self, path: &Path) -> io::Result<()> {
+        self.cpu.memory.load_boot_rom(path)?;
+        self.cpu.reset();
+        self.rewind.clear(&self.cpu);
+        Ok(())
+    }
+
+    /// Saves the screen rebuilt from VRAM as PNG, sprites aren't drawn
+    pub fn screenshot(&self, path: &Path) -> io::Result<()> {
+        let shades = screenshot::render(&self.cpu.memory);
+        fs::write(path, screenshot::encode_png(tilemap::SCREEN_WIDTH as u32, &shades))
+    }
+
+    /// Emulates `cycles` without the UI, stopping early at breakpoints and
+    /// watchpoints. Describes where it stopped
+    pub fn run_for(&mut self, cycles: u64) -> String {
+        let mut ran = 0;
+        while ran < cycles {
+            ran += self.step() as u64;
+            if let Some(reason) = self.stop_reason() {
+                self.stopped(Some(reason));
+                return self.status.clone().unwrap_or_default();
+            }
+        }
+        format!("Ran {} cycles, stopped at {}", ran, Location::mapped(&self.cpu.memory, self.cpu.pc.value))
+    }
+
+    /// Restarts emulation, hard reset reloads the ROM from disk first.
+    /// Breakpoints, watchpoints and symbols are kept
+    fn reset(&mut self, hard: bool) -> Result<String, String> {
+        if hard {
+            let path = self.rom_path.clone().ok_or("no ROM file to reload")?;
+            self.cpu.memory.load(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+        self.cpu.reset();
+        self.rewind.clear(&self.cpu);
+        let status = if hard { "ROM reloaded" } else { "Reset" };
+        self.stopped(Some(status.to_string()));
+        Ok(status.to_string())
+    }
+
+    /// Loads labels from RGBDS or no$gmb symbol file, usually placed next to the ROM.
+    /// Lines that couldn't be parsed are logged to the console and returned
+    pub fn load_symbols(&mut self, path: &Path) -> io::Result<Vec<String>> {
+        self.symbols = Symbols::load(path)?;
+        let warnings: Vec<String> = self.symbols
+            .warnings()
+            .iter()
+            .map(|w| format!("{}: {}", path.display(), w))
+            .collect();
+        for warning in &warnings {
+            self.console.log(LogLine::Error(warning.clone()));
+        }
+        Ok(warnings)
+    }
+
+    /// Checks watchpoint hits of the last instruction and breakpoint at PC against
+    /// their conditions, describes the first reason execution should stop
+    fn stop_reason(&mut self) -> Option<String> {
+        for hit in self.cpu.memory.watchpoints.take_hits() {
+            let condition = self.watch_conditions.entry(hit.watchpoint.clone()).or_default();
+            match condition.check(&self.cpu) {
+                Ok(false) => {},
+                Ok(true) => return Some(format!(
+                    "Watchpoint({}) ${:04X}: ${:02X} -> ${:02X} by ${:04X}",
+                    hit.watchpoint.kind, hit.addr, hit.old, hit.new, self.cpu.instruction_pc,
+                )),
+                Err(e) => return Some(format!("Watchpoint condition failed: {}", e)),
+            }
+        }
+        match self.breakpoints.check(&self.cpu) {
+            Ok(false) => None,
+            Ok(true) => Some(format!("Breakpoint hit at {}", Location::mapped(&self.cpu.memory, self.cpu.pc.value))),
+            Err(e) => Some(format!("Breakpoint condition failed: {}", e)),
+        }
+    }
+
+    /// Toggles watchpoint entered as `start[-end] [r|w|c] [if <expr>] [count N]`
+    fn toggle_watchpoint(&mut self, text: &str) -> Result<String, String> {
+        let (watch, condition) = split_condition(text);
+        let watchpoint = parse_watchpoint(watch, &self.symbols, &self.cpu.memory)
+            .ok_or_else(|| format!("invalid watchpoint {}", watch))?;
+        let condition = Condition::parse(condition, &self.symbols)?;
+        let range = format!("${:04X}-${:04X}", watchpoint.range.start(), watchpoint.range.end());
+        if self.cpu.memory.watchpoints.toggle(watchpoint.clone()) {
+            self.watch_conditions.insert(watchpoint, condition);
+            Ok(format!("Watchpoint added at {}", range))
+        } else {
+            self.watch_conditions.remove(&watchpoint);
+            Ok(format!("Watchpoint removed at {}", range))
+        }
+    }
+
+    /// Sets breakpoint entered as `location [if <expr>] [count N]`, toggles it if
+    /// there is no condition
+    fn set_breakpoint(&mut self, text: &str) -> Result<String, String> {
+        let (location, condition) = split_condition(text);
+        let location = self.location(location)?;
+        if !condition.trim().is_empty() {
+            self.breakpoints.set(location, Condition::parse(condition, &self.symbols)?);
+            Ok(format!("Breakpoint set at {}", location))
+        } else if self.breakpoints.toggle(location) {
+            Ok(format!("Breakpoint added at {}", location))
+        } else {
+            Ok(format!("Breakpoint removed at {}", location))
+        }
+    }
+
+    /// Evaluates expression with current CPU state
+    fn print(&self, text: &str) -> Result<String, String> {
+        let value = self.eval(text)?;
+        Ok(match value {
+            0..=0xFF => format!("{} = ${:02X} ({})", text, value, value),
+            0x100..=0xFFFF => format!("{} = ${:04X} ({})", text, value, value),
+            _ => format!("{} = {}", text, value),
+        })
+    }
+
     pub fn run(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        self.cpu.check_table();
+        // leave raw mode before a panic is reported, so the message is readable
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let _ = disable_raw_mode();
+            default_hook(info);
+        }));
         enable_raw_mode().expect("can run in raw mode");
         let (tx, rx) = mpsc::channel();
         let tick_rate = Duration::from_millis(200);
@@ -57,10 +822,8 @@ impl Debugger {
                     }
                 }
 
-                if last_tick.elapsed() >= tick_rate {
-                    if let Ok(_) = tx.send(Event::Tick) {
-                        last_tick = Instant::now();
-                    }
+                if last_tick.elapsed() >= tick_rate && tx.send(Event::Tick).is_ok() {
+                    last_tick = Instant::now();
                 }
             }
         });
@@ -70,13 +833,27 @@ impl Debugger {
         let mut terminal = Terminal::new(backend)?;
         terminal.clear()?;
 
-        let menu_titles = vec!["CPU", "VRAM", "Memory"];
-        let mut active_menu_item = MenuItem::CPU;
+        let menu_titles = ["CPU", "VRAM", "Memory", "Tilemap", "OAM"];
 
         let mut asm_list_state = ListState::default();
-        let asm_map = self.cpu.disassemble();
+        let disassembler = Disassembler::new();
+        // code reached at runtime that static analysis missed(e.g. routines copied to RAM)
+        let mut asm_entries: Vec<u16> = Vec::new();
+        let mut asm_map = disassembler.disassemble_memory(&self.cpu.memory, &asm_entries);
+
+        // keys go to the console input while it is focused
+        let mut console_focused = false;
 
         loop {
+            // rewinding and loading states may bring back code patched in RAM
+            if std::mem::take(&mut self.replaced) {
+                asm_map = disassembler.disassemble_memory(&self.cpu.memory, &asm_entries);
+            }
+            if asm_map.get(self.cpu.pc.value).is_none() {
+                asm_entries.push(self.cpu.pc.value);
+                asm_map = disassembler.disassemble_memory(&self.cpu.memory, &asm_entries);
+            }
+
             terminal.draw(|rect| {
                 let size = rect.size();
                 let chunks = Layout::default()
@@ -86,13 +863,12 @@ impl Debugger {
                         [
                             Constraint::Length(3),
                             Constraint::Min(2),
+                            Constraint::Length(CONSOLE_HEIGHT),
                             Constraint::Length(3),
                         ]
                         .as_ref(),
                     )
                     .split(size);
-                
-                let controls = ui::build_cpu_controls();
 
                 let menu = menu_titles
                     .iter()
@@ -111,17 +887,18 @@ impl Debugger {
                     .collect();
 
                 let tabs = Tabs::new(menu)
-                    .select(active_menu_item.into())
+                    .select(self.tab.into())
                     .block(Block::default().title("Menu").borders(Borders::ALL))
                     .style(Style::default().fg(Color::White))
                     .highlight_style(Style::default().fg(Color::Yellow))
                     .divider(Span::raw("|"));
                 
                 rect.render_widget(tabs, chunks[0]);
-                rect.render_widget(controls, chunks[2]);
+                rect.render_widget(ui::build_console(&self.console, console_focused, chunks[2].height), chunks[2]);
+                rect.render_widget(ui::build_cpu_controls(self.run.is_some(), self.status.as_deref()), chunks[3]);
 
-                match active_menu_item {
-                    MenuItem::CPU => {
+                match self.tab {
+                    MenuItem::Cpu => {
                         let cpu_chunks = Layout::default()
                         .direction(Direction::Horizontal)
                         .constraints(
@@ -131,28 +908,86 @@ impl Debugger {
                         let registers_chunks = Layout::default()
                             .direction(Direction::Vertical)
                             .constraints(
-                                [Constraint::Percentage(50), Constraint::Percentage(50)].as_ref()
+                                [Constraint::Percentage(50), Constraint::Percentage(30), Constraint::Percentage(20)].as_ref()
                             )
                             .split(cpu_chunks[1]);
                         
                         let (asm, register, stack) = ui::render_cpu(
                             &asm_map,
+                            &self.symbols,
+                            &self.breakpoints,
+                            self.asm_cursor,
                             cpu_chunks[0].height,
                             &self.cpu,
                         );
+                        let stack_chunks = Layout::default()
+                            .direction(Direction::Horizontal)
+                            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+                            .split(registers_chunks[1]);
                         asm_list_state.select(Some(asm.1));
                         rect.render_stateful_widget(asm.0, cpu_chunks[0], &mut asm_list_state);
                         rect.render_widget(register, registers_chunks[0]);
-                        rect.render_widget(stack, registers_chunks[1]);
+                        rect.render_widget(stack, stack_chunks[0]);
+                        rect.render_widget(ui::render_call_stack(&self.cpu, &self.symbols), stack_chunks[1]);
+                        rect.render_widget(
+                            ui::render_breakpoints(
+                                &self.breakpoints,
+                                &self.cpu.memory.watchpoints,
+                                &self.watch_conditions,
+                                &self.symbols,
+                            ),
+                            registers_chunks[2],
+                        );
+                    },
+                    MenuItem::Vram => { 
+                        ui::build_vram(&self.tiles, &self.cpu.memory, &chunks[1], rect);
                     },
-                    MenuItem::VRAM => { 
-                        ui::build_vram(&self.cpu, &chunks[1], rect);
+                    MenuItem::Oam => {
+                        ui::build_oam(self.sprite, &self.cpu.memory, &chunks[1], rect);
+                    },
+                    MenuItem::Tilemap => {
+                        ui::build_tilemap(&self.tilemap, &self.cpu.memory, &chunks[1], rect);
+                    },
+                    MenuItem::Memory => {
+                        rect.render_widget(ui::build_memory(&self.hexview, &self.cpu.memory, chunks[1].height), chunks[1]);
                     },
-                    MenuItem::Memory => { }
                 }
             })?;
 
-            match rx.recv()? {
+            self.run_frame();
+
+            let event = if self.run.is_some() {
+                match rx.try_recv() {
+                    Ok(event) => event,
+                    Err(TryRecvError::Empty) => continue,
+                    Err(e) => return Err(e.into()),
+                }
+            } else {
+                rx.recv()?
+            };
+
+            match event {
+                Event::Input(event) if console_focused => match event.code {
+                    KeyCode::Esc => console_focused = false,
+                    KeyCode::Backspace => { self.console.input.pop(); },
+                    KeyCode::Char(c) => self.console.input.push(c),
+                    KeyCode::Up => self.console.history_prev(),
+                    KeyCode::Down => self.console.history_next(),
+                    KeyCode::Tab => self.console.complete(self.symbols.names().chain(REGISTER_NAMES)),
+                    KeyCode::Enter => {
+                        let line = self.console.submit();
+                        if !line.trim().is_empty() {
+                            self.execute(&line);
+                            // commands may change code
+                            asm_map = disassembler.disassemble_memory(&self.cpu.memory, &asm_entries);
+                        }
+                    },
+                    _ => {}
+                },
+                Event::Input(event) if matches!(self.tab, MenuItem::Memory) && self.memory_key(event.code) => {},
+                Event::Input(event) if matches!(self.tab, MenuItem::Vram) && self.vram_key(event.code) => {},
+                Event::Input(event) if matches!(self.tab, MenuItem::Tilemap) && self.tilemap_key(event.code) => {},
+                Event::Input(event) if matches!(self.tab, MenuItem::Oam) && self.oam_key(event.code) => {},
                 Event::Input(event) => match event.code {
                     KeyCode::Char('q') | KeyCode::Esc => {
                         disable_raw_mode()?;
@@ -160,15 +995,61 @@ impl Debugger {
                         terminal.show_cursor()?;
                         break;
                     }
-                    KeyCode::Char('c') => active_menu_item = MenuItem::CPU,
-                    KeyCode::Char('m') => active_menu_item = MenuItem::Memory,
-                    KeyCode::Char('v') => active_menu_item = MenuItem::VRAM,
-                    KeyCode::Char(' ') => {
-                        self.cpu.clock();
-                        while self.cpu.cycles != 0 {
-                            self.cpu.clock();
+                    KeyCode::Char('c') => self.tab = MenuItem::Cpu,
+                    KeyCode::Char('m') => self.tab = MenuItem::Memory,
+                    KeyCode::Char('v') => self.tab = MenuItem::Vram,
+                    KeyCode::Char('t') => self.tab = MenuItem::Tilemap,
+                    KeyCode::Char('o') => self.tab = MenuItem::Oam,
+                    KeyCode::Char(':') => console_focused = true,
+                    KeyCode::Char(c @ ('a' | 'B' | 'w' | 'p')) => {
+                        let command = match c {
+                            'a' => "patch ",
+                            'B' => "break ",
+                            'w' => "watch ",
+                            _ => "print ",
+                        };
+                        self.console.input = command.to_string();
+                        console_focused = true;
+                    },
+                    KeyCode::Char('b') => {
+                        let addr = self.asm_cursor.unwrap_or(self.cpu.pc.value);
+                        if let Some(line) = asm_map.get(addr) {
+                            self.breakpoints.toggle(line.location);
                         }
                     },
+                    KeyCode::Up | KeyCode::Down => {
+                        let index = asm_map.index_of(self.asm_cursor.unwrap_or(self.cpu.pc.value)).unwrap_or(0);
+                        let index = match event.code {
+                            KeyCode::Up => index.saturating_sub(1),
+                            _ => index + 1,
+                        };
+                        if let Some((addr, _)) = asm_map.lines.get_index(index) {
+                            self.asm_cursor = Some(*addr);
+                        }
+                    },
+                    KeyCode::Char('g') if self.run.is_some() => self.run = None,
+                    KeyCode::Char('g') => self.resume(RunMode::Continue),
+                    KeyCode::Char('n') => self.step_over(),
+                    KeyCode::Char('u') => self.resume(RunMode::step_out(&self.cpu)),
+                    KeyCode::Char('h') => {
+                        let addr = self.asm_cursor.unwrap_or(self.cpu.pc.value);
+                        if let Some(line) = asm_map.get(addr) {
+                            self.resume(RunMode::ToLocation(line.location));
+                        }
+                    },
+                    KeyCode::Char('e') => self.execute("export"),
+                    KeyCode::Char(c @ ('z' | 'Z')) => self.rewind_key(c == 'Z'),
+                    KeyCode::Char('G') => self.execute("reverse-continue"),
+                    KeyCode::F(5) => self.execute("save-state"),
+                    KeyCode::F(9) => self.execute("load-state"),
+                    KeyCode::Char(c @ ('r' | 'R')) => {
+                        self.execute(if c == 'R' { "reset hard" } else { "reset" });
+                        asm_entries.clear();
+                    },
+                    KeyCode::Char(' ') => {
+                        self.asm_cursor = None;
+                        self.status = self.step_n(1);
+                    },
                     _ => {}
                 },
                 Event::Tick => {}
@@ -0,0 +1,163 @@
+use crate::core::memory::Memory;
+
+use super::{tilemap::LCDC, tiles::{TilePalette, tile_address}};
+
+/*
+    Object attribute memory
+
+    0xFE00-0xFE9F holds 40 sprites of 4 bytes:
+        0 - Y position + 16
+        1 - X position + 8
+        2 - tile number, always in 0x8000 mode
+        3 - flags
+            bit 7    - background and window colors 1-3 are drawn over the sprite
+            bit 6    - vertical flip
+            bit 5    - horizontal flip
+            bit 4    - DMG palette, OBP0/OBP1
+            bit 3    - CGB VRAM bank
+            bits 2-0 - CGB palette
+
+    LCDC bit 2 selects 8x16 sprites, which use tile number & 0xFE for the top
+    half and tile number | 0x01 for the bottom one. The PPU draws only the
+    first 10 sprites in OAM order that overlap a line.
+*/
+
+pub const OAM: usize = 0xFE00;
+pub const SPRITE_COUNT: usize = 40;
+const SPRITES_PER_LINE: usize = 10;
+const LY: usize = 0xFF44;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sprite {
+    pub y: u8,
+    pub x: u8,
+    pub tile: u8,
+    pub flags: u8,
+}
+
+impl Sprite {
+    pub fn read(memory: &Memory, index: usize) -> Sprite {
+        let addr = OAM + index * 4;
+        Sprite {
+            y: memory.peek(addr),
+            x: memory.peek(addr + 1),
+            tile: memory.peek(addr + 2),
+            flags: memory.peek(addr + 3),
+        }
+    }
+
+    pub fn behind_background(&self) -> bool { self.flags & 0x80 != 0 }
+    pub fn y_flip(&self) -> bool { self.flags & 0x40 != 0 }
+    pub fn x_flip(&self) -> bool { self.flags & 0x20 != 0 }
+    pub fn cgb_bank(&self) -> u8 { (self.flags >> 3) & 1 }
+    pub fn cgb_palette(&self) -> u8 { self.flags & 0x07 }
+
+    pub fn palette(&self) -> TilePalette {
+        if self.flags & 0x10 != 0 { TilePalette::Obp1 } else { TilePalette::Obp0 }
+    }
+
+    /// Whether no pixel of the sprite is on the screen
+    pub fn is_offscreen(&self, height: u8) -> bool {
+        self.x == 0 || self.x >= 168 || self.y as u16 + height as u16 <= 16 || self.y >= 160
+    }
+
+    /// Whether the sprite overlaps line `ly`
+    pub fn on_line(&self, ly: u8, height: u8) -> bool {
+        let top = self.y as i16 - 16;
+        (top..top + height as i16).contains(&(ly as i16))
+    }
+
+    /// Color numbers of the sprite rows with flips applied
+    pub fn pixels(&self, memory: &Memory, height: u8) -> Vec<[u8; 8]> {
+        let tile = if height == 16 { self.tile & 0xFE } else { self.tile };
+        let mut rows: Vec<[u8; 8]> = (0..height as usize)
+            .map(|y| {
+                let addr = tile_address(tile as usize).1 as usize + y * 2;
+                let (low, high) = (memory.peek(addr), memory.peek(addr + 1));
+                let mut row = [0; 8];
+                for (x, pixel) in row.iter_mut().enumerate() {
+                    let bit = 7 - x;
+                    *pixel = ((high >> bit) & 1) << 1 | ((low >> bit) & 1);
+                }
+                if self.x_flip() {
+                    row.reverse();
+                }
+                row
+            })
+            .collect();
+        if self.y_flip() {
+            rows.reverse();
+        }
+        rows
+    }
+}
+
+/// Sprite height selected by LCDC
+pub fn sprite_height(memory: &Memory) -> u8 {
+    if memory.peek(LCDC) & 0x04 != 0 { 16 } else { 8 }
+}
+
+pub fn current_line(memory: &Memory) -> u8 {
+    memory.peek(LY)
+}
+
+/// Sprites overlapping line `ly` that the PPU doesn't draw because of the
+/// per-line limit
+pub fn dropped(memory: &Memory, ly: u8) -> Vec<usize> {
+    let height = sprite_height(memory);
+    (0..SPRITE_COUNT)
+        .filter(|i| Sprite::read(memory, *i).on_line(ly, height))
+        .skip(SPRITES_PER_LINE)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sprite() {
+        let mut mem = Memory::new();
+        mem[0xFE04u16] = 16;
+        mem[0xFE05u16] = 8;
+        mem[0xFE06u16] = 0x03;
+        mem[0xFE07u16] = 0x78;
+        let sprite = Sprite::read(&mem, 1);
+        assert_eq!(sprite, Sprite { y: 16, x: 8, tile: 3, flags: 0x78 });
+        assert!(!sprite.behind_background());
+        assert!(sprite.y_flip() && sprite.x_flip());
+        assert_eq!(sprite.palette(), TilePalette::Obp1);
+        assert_eq!(sprite.cgb_bank(), 1);
+        assert!(!sprite.is_offscreen(8));
+        assert!(Sprite { x: 0, ..sprite }.is_offscreen(8));
+        assert!(Sprite { y: 8, ..sprite }.is_offscreen(8));
+        assert!(!Sprite { y: 8, ..sprite }.is_offscreen(16));
+
+        // tile 3: top row 0xF0, both flips put it to the bottom right
+        mem[0x8030u16] = 0xF0;
+        let pixels = sprite.pixels(&mem, 8);
+        assert_eq!(pixels[7], [0, 0, 0, 0, 1, 1, 1, 1]);
+        // tile 2 is the top half of an 8x16 sprite
+        mem[0x8020u16] = 0x01;
+        let pixels = Sprite { flags: 0, ..sprite }.pixels(&mem, 16);
+        assert_eq!(pixels.len(), 16);
+        assert_eq!(pixels[0], [0, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(pixels[8], [1, 1, 1, 1, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_line_limit() {
+        let mut mem = Memory::new();
+        for i in 0..12 {
+            mem[OAM + i * 4] = 20 + (i % 2) as u8;
+            mem[OAM + i * 4 + 1] = 50;
+        }
+        assert!(Sprite::read(&mem, 0).on_line(4, 8));
+        assert!(!Sprite::read(&mem, 0).on_line(12, 8));
+        assert_eq!(dropped(&mem, 5), vec![10, 11]);
+        assert_eq!(dropped(&mem, 4), vec![]);
+        assert_eq!(dropped(&mem, 12), vec![]);
+        mem[LCDC] = 0x04;
+        assert_eq!(dropped(&mem, 12), vec![10, 11]);
+    }
+}
@@ -0,0 +1,452 @@
+use std::collections::VecDeque;
+
+use crate::{core::state::MachineState, cpu::CPU};
+
+use super::FRAME_CYCLES;
+
+/*
+    Rewind snapshots
+
+    Snapshots are encoded save states taken every `interval` frames. The newest
+    one is kept whole, every older one is stored as a delta against the next
+    newer snapshot: XOR of the two encodings, run length encoded as
+        u16 count of zero bytes, u16 count of literal bytes, literal bytes
+    repeated until the end. Most of memory doesn't change between frames so
+    deltas are small, and dropping the oldest snapshot needs no decoding.
+
+    Positions are counted in CPU steps(an instruction, interrupt dispatch or
+    HALT idle) since the buffer was cleared. Seeking restores the nearest
+    snapshot before the position and replays steps up to it. Emulation is
+    deterministic given the inputs, so joypad changes go through `input`,
+    which logs the buttons with the position they changed at. Replay applies
+    them again at the same positions. Seeking back drops inputs after the
+    target, history from there on is made again.
+*/
+
+/// Frames between snapshots when none is given
+pub const REWIND_INTERVAL: u64 = 4;
+/// Snapshots kept when no amount is given, 10 seconds with the default interval
+pub const REWIND_CAPACITY: usize = 150;
+
+/// Run length encodes XOR of `a` and `b`, which have the same length
+fn compress(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut xor = a.iter().zip(b).map(|(a, b)| a ^ b).peekable();
+    while xor.peek().is_some() {
+        let mut zeros = 0u16;
+        while zeros < u16::MAX && xor.next_if_eq(&0).is_some() {
+            zeros += 1;
+        }
+        let mut literals = Vec::new();
+        while literals.len() < u16::MAX as usize {
+            match xor.next_if(|b| *b != 0) {
+                Some(b) => literals.push(b),
+                None => break,
+            }
+        }
+        out.extend_from_slice(&zeros.to_le_bytes());
+        out.extend_from_slice(&(literals.len() as u16).to_le_bytes());
+        out.extend_from_slice(&literals);
+    }
+    out
+}
+
+/// Applies delta made by `compress` to `data` in place
+fn decompress(delta: &[u8], data: &mut [u8]) {
+    let (mut i, mut pos) = (0, 0);
+    while i + 4 <= delta.len() {
+        let zeros = u16::from_le_bytes([delta[i], delta[i + 1]]) as usize;
+        let count = u16::from_le_bytes([delta[i + 2], delta[i + 3]]) as usize;
+        i += 4;
+        pos += zeros;
+        for (place, b) in data[pos..pos + count].iter_mut().zip(&delta[i..i + count]) {
+            *place ^= b;
+        }
+        pos += count;
+        i += count;
+    }
+}
+
+struct Snapshot {
+    /// Steps executed before the snapshot was taken
+    step: u64,
+    cycles: u64,
+    /// Delta against the next snapshot, empty for the newest one
+    delta: Vec<u8>,
+}
+
+/// ## Rewind buffer
+/// Ring of periodic snapshots, `record` has to be called after every CPU step
+pub struct Rewind {
+    /// Frames between snapshots
+    pub interval: u64,
+    capacity: usize,
+    snapshots: VecDeque<Snapshot>,
+    /// Encoded state of the newest snapshot
+    latest: Vec<u8>,
+    /// Joypad buttons with the step they were pressed at, oldest first
+    inputs: VecDeque<(u64, u8)>,
+    /// Steps executed since the buffer was cleared
+    pub step: u64,
+    pub cycles: u64,
+}
+
+impl Rewind {
+    pub fn new(interval: u64, capacity: usize) -> Rewind {
+        Rewind {
+            interval: interval.max(1),
+            capacity: capacity.max(1),
+            snapshots: VecDeque::new(),
+            latest: Vec::new(),
+            inputs: VecDeque::new(),
+            step: 0,
+            cycles: 0,
+        }
+    }
+
+    /// Forgets history, current state becomes the first snapshot. Needed when the
+    /// machine is replaced(reset, loaded state)
+    pub fn clear(&mut self, cpu: &CPU) {
+        self.snapshots.clear();
+        self.inputs.clear();
+        self.step = 0;
+        self.cycles = 0;
+        self.push(cpu);
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.cycles / FRAME_CYCLES as u64
+    }
+
+    /// Earliest step that can be restored
+    pub fn first_step(&self) -> u64 {
+        self.snapshots.front().map_or(self.step, |s| s.step)
+    }
+
+    /// Memory used by snapshots
+    pub fn size(&self) -> usize {
+        self.latest.len() + self.snapshots.iter().map(|s| s.delta.len()).sum::<usize>()
+    }
+
+    /// Counts a step that took `cycles`, takes a snapshot at every `interval` frames
+    pub fn record(&mut self, cpu: &CPU, cycles: u32) {
+        let frame = self.frame();
+        self.step += 1;
+        self.cycles += cycles as u64;
+        if self.frame() != frame && self.frame().is_multiple_of(self.interval) {
+            self.push(cpu);
+        }
+    }
+
+    /// Sets held joypad buttons, logged so replay presses them at the same step
+    pub fn input(&mut self, cpu: &mut CPU, buttons: u8) {
+        if self.inputs.back().is_some_and(|(step, _)| *step == self.step) {
+            self.inputs.pop_back();
+        }
+        self.inputs.push_back((self.step, buttons));
+        cpu.memory.set_buttons(buttons);
+    }
+
+    /// Buttons logged at `step`
+    pub fn input_at(&self, step: u64) -> Option<u8> {
+        let i = self.inputs.binary_search_by_key(&step, |(s, _)| *s).ok()?;
+        Some(self.inputs[i].1)
+    }
+
+    /// Takes a snapshot now, so state changed other than by stepping(edits made in
+    /// the debugger) survives seeking
+    pub fn mark(&mut self, cpu: &CPU) {
+        if self.snapshots.back().is_some_and(|s| s.step == self.step) {
+            self.pop();
+        }
+        self.push(cpu);
+    }
+
+    fn push(&mut self, cpu: &CPU) {
+        let state = cpu.save_state().encode();
+        if let Some(newest) = self.snapshots.back_mut() {
+            newest.delta = compress(&state, &self.latest);
+        }
+        self.latest = state;
+        self.snapshots.push_back(Snapshot { step: self.step, cycles: self.cycles, delta: Vec::new() });
+        if self.snapshots.len() > self.capacity {
+            self.snapshots.pop_front();
+            let first = self.first_step();
+            while self.inputs.front().is_some_and(|(step, _)| *step < first) {
+                self.inputs.pop_front();
+            }
+        }
+    }
+
+    /// Encoded snapshots from the newest one, with the steps they were taken at
+    pub fn history(&self) -> impl Iterator<Item = (u64, Vec<u8>)> + '_ {
+        let mut state = self.latest.clone();
+        self.snapshots.iter().rev().enumerate().map(move |(i, snapshot)| {
+            if i > 0 {
+                decompress(&snapshot.delta, &mut state);
+            }
+            (snapshot.step, state.clone())
+        })
+    }
+
+    /// Drops the newest snapshot, the one before it becomes whole
+    fn pop(&mut self) {
+        self.snapshots.pop_back();
+        if let Some(newest) = self.snapshots.back_mut() {
+            decompress(&newest.delta, &mut self.latest);
+            newest.delta.clear();
+        }
+    }
+
+    /// Restores the newest snapshot taken at or before `step`, dropping newer ones
+    fn restore(&mut self, cpu: &mut CPU, step: u64) -> Result<(), String> {
+        while self.snapshots.back().is_some_and(|s| s.step > step) {
+            self.pop();
+        }
+        let newest = self.snapshots.back().ok_or("rewind buffer is empty")?;
+        let state = MachineState::decode(&self.latest).map_err(|e| e.to_string())?;
+        cpu.load_state(&state).map_err(|e| e.to_string())?;
+        self.step = newest.step;
+        self.cycles = newest.cycles;
+        Ok(())
+    }
+
+    /// Steps until `done` pressing logged buttons, without writing trace or
+    /// reporting watchpoints
+    fn replay(&mut self, cpu: &mut CPU, done: impl Fn(&Rewind) -> bool) {
+        let trace = cpu.trace.take();
+        loop {
+            if let Some(buttons) = self.input_at(self.step) {
+                cpu.memory.set_buttons(buttons);
+            }
+            if done(self) {
+                break;
+            }
+            let cycles = cpu.step();
+            self.record(cpu, cycles);
+        }
+        cpu.trace = trace;
+        cpu.memory.watchpoints.take_hits();
+    }
+
+    /// Brings `cpu` to the state after `step` steps, by restoring the nearest
+    /// snapshot and replaying. Newer snapshots are discarded, `step` has to be in
+    /// the buffer
+    pub fn seek(&mut self, cpu: &mut CPU, step: u64) -> Result<(), String> {
+        if step < self.first_step() || step > self.step {
+            return Err(format!("step {} is out of the rewind buffer({}-{})", step, self.first_step(), self.step));
+        }
+        self.restore(cpu, step)?;
+        self.replay(cpu, |r| r.step >= step);
+        self.forget_inputs();
+        Ok(())
+    }
+
+    /// Goes back to the first step of frame `frame`, or to the oldest snapshot
+    /// when the frame is out of the buffer
+    pub fn seek_frame(&mut self, cpu: &mut CPU, frame: u64) -> Result<(), String> {
+        let target = frame * FRAME_CYCLES as u64;
+        let step = self
+            .snapshots
+            .iter()
+            .rev()
+            .find(|s| s.cycles <= target)
+            .or_else(|| self.snapshots.front())
+            .ok_or("rewind buffer is empty")?
+            .step;
+        self.restore(cpu, step)?;
+        self.replay(cpu, |r| r.cycles >= target);
+        self.forget_inputs();
+        Ok(())
+    }
+
+    /// Drops inputs after the current step, they belong to the history that was
+    /// left by seeking
+    fn forget_inputs(&mut self) {
+        while self.inputs.back().is_some_and(|(step, _)| *step > self.step) {
+            self.inputs.pop_back();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts up in WRAM forever, with the timer interrupt incrementing B and
+    /// the joypad one incrementing C
+    fn program() -> CPU {
+        let mut rom = vec![0x00; 0x8000];
+        let code: [u8; 21] = [
+            // LD A,$05; LDH [TAC],A; LD A,$14; LDH [IE],A; LD HL,$C000; EI
+            0x3E, 0x05, 0xE0, 0x07, 0x3E, 0x14, 0xE0, 0xFF, 0x21, 0x00, 0xC0, 0xFB,
+            // LD A,$10; LDH [P1],A - select action buttons
+            0x3E, 0x10, 0xE0, 0x00,
+            // loop: INC [HL]; INC L; JR loop
+            0x34, 0x2C, 0x18, 0xFC, 0x00,
+        ];
+        rom[0x0100..0x0100 + code.len()].copy_from_slice(&code);
+        // timer interrupt: INC B; RETI
+        rom[0x0050] = 0x04;
+        rom[0x0051] = 0xD9;
+        // joypad interrupt: INC C; RETI
+        rom[0x0060] = 0x0C;
+        rom[0x0061] = 0xD9;
+        let mut cpu = CPU::new();
+        cpu.memory.load_rom(rom);
+        cpu
+    }
+
+    fn run(cpu: &mut CPU, rewind: &mut Rewind, steps: u64) {
+        for _ in 0..steps {
+            let cycles = cpu.step();
+            rewind.record(cpu, cycles);
+        }
+    }
+
+    #[test]
+    fn test_compress() {
+        let a = vec![0u8; 70000];
+        let mut b = a.clone();
+        b[3] = 1;
+        b[4] = 2;
+        b[69999] = 3;
+        let delta = compress(&a, &b);
+        assert!(delta.len() < 32);
+        let mut restored = a.clone();
+        decompress(&delta, &mut restored);
+        assert_eq!(restored, b);
+        assert!(compress(&a, &a).len() <= 8);
+    }
+
+    /// Replaying from a snapshot with the same input log has to reproduce the
+    /// original run exactly, reverse stepping relies on it
+    #[test]
+    fn test_deterministic() {
+        let steps = 50000;
+        // A pressed, released, A and B pressed
+        let inputs = [(100, 0x01), (20000, 0x00), (20001, 0x03)];
+        let record = |cpu: &mut CPU| -> Vec<_> {
+            (0..steps)
+                .map(|step| {
+                    if let Some((_, buttons)) = inputs.iter().find(|(s, _)| *s == step) {
+                        cpu.memory.set_buttons(*buttons);
+                    }
+                    cpu.step();
+                    cpu.trace_line()
+                })
+                .collect()
+        };
+        let mut cpu = program();
+        let start = cpu.save_state();
+        let first = record(&mut cpu);
+        let end = cpu.save_state();
+
+        let mut other = program();
+        assert_eq!(record(&mut other), first);
+        assert_eq!(other.save_state(), end);
+
+        cpu.load_state(&start).unwrap();
+        assert_eq!(record(&mut cpu), first);
+        assert_eq!(cpu.save_state(), end);
+        // inputs reached the program through joypad interrupts
+        assert_eq!(cpu.reg_bc.lo(), 0x15);
+
+        // the rewind buffer replays the same log to the same state
+        let mut cpu = program();
+        let mut rewind = Rewind::new(1, 16);
+        rewind.clear(&cpu);
+        let mut middle = None;
+        for step in 0..steps {
+            if let Some((_, buttons)) = inputs.iter().find(|(s, _)| *s == step) {
+                rewind.input(&mut cpu, *buttons);
+            }
+            run(&mut cpu, &mut rewind, 1);
+            if step == 20010 {
+                middle = Some(cpu.save_state());
+            }
+        }
+        assert_eq!(cpu.save_state(), end);
+        // replays from a snapshot before the last inputs
+        assert!(rewind.snapshots.iter().rev().find(|s| s.step <= 20011).unwrap().step < 20000);
+        rewind.seek(&mut cpu, 20011).unwrap();
+        assert_eq!(Some(cpu.save_state()), middle);
+    }
+
+    #[test]
+    fn test_history() {
+        let mut cpu = program();
+        let mut rewind = Rewind::new(1, 4);
+        rewind.clear(&cpu);
+        let mut states = vec![(0, cpu.save_state().encode())];
+        for _ in 0..3 {
+            let len = rewind.snapshots.len();
+            while rewind.snapshots.len() == len {
+                run(&mut cpu, &mut rewind, 1);
+            }
+            states.push((rewind.step, cpu.save_state().encode()));
+        }
+        states.reverse();
+        assert_eq!(rewind.history().collect::<Vec<_>>(), states);
+    }
+
+    #[test]
+    fn test_inputs() {
+        let mut cpu = program();
+        let mut rewind = Rewind::new(1, 8);
+        rewind.clear(&cpu);
+        run(&mut cpu, &mut rewind, 1000);
+        rewind.input(&mut cpu, 0x01);
+        run(&mut cpu, &mut rewind, 1);
+        let pressed = cpu.save_state();
+        run(&mut cpu, &mut rewind, 30000);
+        let later = cpu.save_state();
+        assert_eq!(cpu.reg_bc.lo(), 0x14);
+
+        // replay from the first snapshot presses A at the same step
+        rewind.seek(&mut cpu, 1001).unwrap();
+        assert_eq!(cpu.save_state(), pressed);
+        run(&mut cpu, &mut rewind, 30000);
+        assert_eq!(cpu.save_state(), later);
+
+        // going back before the press forgets it
+        rewind.seek(&mut cpu, 999).unwrap();
+        run(&mut cpu, &mut rewind, 2);
+        assert_eq!(cpu.memory.buttons(), 0x00);
+        assert_eq!(cpu.reg_bc.lo(), 0x13);
+    }
+
+    #[test]
+    fn test_seek() {
+        let mut cpu = program();
+        let mut rewind = Rewind::new(1, 4);
+        rewind.clear(&cpu);
+        run(&mut cpu, &mut rewind, 30000);
+        let expected = cpu.save_state();
+
+        run(&mut cpu, &mut rewind, 20000);
+        rewind.seek(&mut cpu, 30000).unwrap();
+        assert_eq!(cpu.save_state(), expected);
+        assert_eq!(rewind.step, 30000);
+
+        // edit made at the current step replaces the snapshot taken there
+        cpu.memory[0xD000u16] = 0x42;
+        rewind.mark(&cpu);
+        rewind.mark(&cpu);
+        run(&mut cpu, &mut rewind, 100);
+        rewind.seek(&mut cpu, 30050).unwrap();
+        assert_eq!(cpu.memory.peek(0xD000), 0x42);
+        rewind.seek(&mut cpu, 29000).unwrap();
+        assert_eq!(cpu.memory.peek(0xD000), 0x00);
+
+        // history before the oldest snapshot is gone
+        run(&mut cpu, &mut rewind, 100000);
+        assert!(rewind.seek(&mut cpu, 30000).is_err());
+        assert!(rewind.seek(&mut cpu, rewind.step + 1).is_err());
+
+        let frame = rewind.frame() - 1;
+        rewind.seek_frame(&mut cpu, frame).unwrap();
+        assert_eq!(rewind.frame(), frame);
+        assert!(rewind.cycles - frame * (FRAME_CYCLES as u64) < 24);
+    }
+}
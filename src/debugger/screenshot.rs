@@ -0,0 +1,155 @@
+use crate::core::memory::Memory;
+
+use super::{tiles::TilePalette, tilemap::{self, LCDC, SCX, SCY, WX, WY, SCREEN_WIDTH, SCREEN_HEIGHT}};
+
+/*
+    Screenshots
+
+    There is no PPU yet, so the screen is rebuilt from VRAM the way the tilemap
+    tab draws it: the background from SCX, SCY with the window over it, shaded
+    with BGP. Sprites aren't drawn, and mid-frame register changes are lost.
+
+    The image is written as an 8 bit grayscale PNG with uncompressed(stored)
+    deflate blocks, which needs nothing but CRC-32 and Adler-32.
+*/
+
+const PNG_SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+/// Largest payload of a stored deflate block
+const STORED_BLOCK: usize = 0xFFFF;
+
+/// Shades from white(0) to black(3) of the screen, row by row
+pub fn render(memory: &Memory) -> Vec<u8> {
+    let lcdc = memory.peek(LCDC);
+    let mut shades = vec![0; SCREEN_WIDTH as usize * SCREEN_HEIGHT as usize];
+    // LCD or background off
+    if lcdc & 0x81 != 0x81 {
+        return shades;
+    }
+    let background = if lcdc & 0x08 != 0 { 0x9C00 } else { 0x9800 };
+    let window = if lcdc & 0x40 != 0 { 0x9C00 } else { 0x9800 };
+    let (scx, scy) = (memory.peek(SCX), memory.peek(SCY));
+    let (wx, wy) = (memory.peek(WX) as i16 - 7, memory.peek(WY) as i16);
+
+    for y in 0..SCREEN_HEIGHT {
+        for x in 0..SCREEN_WIDTH {
+            let (wx, wy) = (x as i16 - wx, y as i16 - wy);
+            let color = if lcdc & 0x20 != 0 && wx >= 0 && wy >= 0 {
+                tilemap::pixel(memory, window, wx as u8, wy as u8)
+            } else {
+                tilemap::pixel(memory, background, x.wrapping_add(scx), y.wrapping_add(scy))
+            };
+            shades[y as usize * SCREEN_WIDTH as usize + x as usize] = TilePalette::Bgp.shade(memory, color);
+        }
+    }
+    shades
+}
+
+/// PNG of `shades`, `width` pixels per row
+pub fn encode_png(width: u32, shades: &[u8]) -> Vec<u8> {
+    let height = shades.len() as u32 / width;
+    let mut raw = Vec::with_capacity(shades.len() + height as usize);
+    for row in shades.chunks(width as usize) {
+        // filter type none
+        raw.push(0);
+        raw.extend(row.iter().map(|shade| 0xFF - shade * 0x55));
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let blocks = raw.chunks(STORED_BLOCK).count();
+    for (i, block) in raw.chunks(STORED_BLOCK).enumerate() {
+        let len = block.len() as u16;
+        zlib.push((i + 1 == blocks) as u8);
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bit grayscale, deflate, no filtering method, no interlace
+    header.extend_from_slice(&[8, 0, 0, 0, 0]);
+
+    let mut out = PNG_SIGNATURE.to_vec();
+    write_chunk(&mut out, b"IHDR", &header);
+    write_chunk(&mut out, b"IDAT", &zlib);
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+fn write_chunk(out: &mut Vec<u8>, tag: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(tag);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &b| {
+        (0..8).fold(crc ^ b as u32, |crc, _| if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 })
+    })
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), &v| {
+        let a = (a + v as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut mem = Memory::new();
+        mem.load_rom(vec![0; 0x8000]);
+        // tile 1 is black, BGP maps color 3 to shade 3
+        for addr in 0x8010..0x8020u16 {
+            mem[addr] = 0xFF;
+        }
+        mem[0xFF47u16] = 0xE4;
+        // map entry of background pixel 8, 0; window uses 0x9C00 filled with tile 1
+        mem[0x9801u16] = 0x01;
+        for addr in 0x9C00..0xA000u16 {
+            mem[addr] = 0x01;
+        }
+        mem[LCDC as u16] = 0x91;
+        mem[SCX as u16] = 0x04;
+        let shades = render(&mem);
+        assert_eq!(&shades[..14], &[0, 0, 0, 0, 3, 3, 3, 3, 3, 3, 3, 3, 0, 0]);
+
+        // window from the middle of the screen
+        mem[LCDC as u16] = 0xF1;
+        mem[WX as u16] = 80 + 7;
+        mem[WY as u16] = 72;
+        let shades = render(&mem);
+        assert_eq!(shades[72 * 160 + 79], 0);
+        assert_eq!(shades[72 * 160 + 80], 3);
+        assert_eq!(shades[71 * 160 + 80], 0);
+
+        mem[LCDC as u16] = 0x00;
+        assert!(render(&mem).iter().all(|&shade| shade == 0));
+    }
+
+    #[test]
+    fn test_png() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+
+        let png = encode_png(2, &[0, 3, 1, 2]);
+        assert!(png.starts_with(PNG_SIGNATURE));
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..24], &[0, 0, 0, 2, 0, 0, 0, 2]);
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]));
+        // rows with filter byte, after the zlib header and stored block header
+        let idat = 8 + 8 + 13 + 4 + 8;
+        assert_eq!(&png[idat..idat + 2], &[0x78, 0x01]);
+        assert_eq!(&png[idat + 7..idat + 13], &[0, 0xFF, 0x00, 0, 0xAA, 0x55]);
+    }
+}
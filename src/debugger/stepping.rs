@@ -0,0 +1,117 @@
+use crate::{cpu::CPU, core::opcodes::Instruction, disasm::Location};
+
+/// What continuous emulation waits for, besides breakpoints and watchpoints
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RunMode {
+    /// Runs until paused
+    Continue,
+    /// Runs until the call being stepped over returns to `addr` with SP back at `sp`
+    StepOver { addr: u16, sp: u16 },
+    /// Runs until a return moves SP above `sp`, leaving the current frame
+    StepOut { sp: u16 },
+    /// Runs until `location` is reached
+    ToLocation(Location),
+}
+
+impl RunMode {
+    /// Step over the instruction at PC, `None` if it isn't a call and a single step will do
+    pub fn step_over(cpu: &CPU) -> Option<RunMode> {
+        let pc = cpu.pc.value;
+        let info = cpu.instruction_at(pc);
+        match info.instruction {
+            Instruction::CALL(..) | Instruction::RST(_) => Some(RunMode::StepOver {
+                addr: pc.wrapping_add(info.length),
+                sp: cpu.stack_pointer.value,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn step_out(cpu: &CPU) -> RunMode {
+        RunMode::StepOut { sp: cpu.stack_pointer.value }
+    }
+
+    /// Checks state after an instruction is executed, returns true once the run is finished
+    pub fn is_done(&self, cpu: &CPU) -> bool {
+        match *self {
+            RunMode::Continue => false,
+            RunMode::StepOver { addr, sp } => cpu.pc.value == addr && cpu.stack_pointer.value >= sp,
+            RunMode::StepOut { sp } => {
+                let returned = matches!(
+                    cpu.instruction_at(cpu.instruction_pc).instruction,
+                    Instruction::RET(_) | Instruction::RETI
+                );
+                returned && cpu.stack_pointer.value > sp
+            },
+            RunMode::ToLocation(location) => Location::mapped(&cpu.memory, cpu.pc.value) == location,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs until `mode` is done, returns amount of instructions executed
+    fn run(cpu: &mut CPU, mode: RunMode) -> usize {
+        let mut steps = 0;
+        loop {
+            cpu.step();
+            steps += 1;
+            if mode.is_done(cpu) || steps == 100 {
+                return steps;
+            }
+        }
+    }
+
+    fn program() -> CPU {
+        let mut cpu = CPU::new();
+        // $0100: CALL $C000; NOP
+        // $C000: CALL $C010; RET
+        // $C010: PUSH AF; POP AF; RET
+        let code: [(u16, &[u8]); 3] = [
+            (0x0100, &[0xCD, 0x00, 0xC0, 0x00]),
+            (0xC000, &[0xCD, 0x10, 0xC0, 0xC9]),
+            (0xC010, &[0xF5, 0xF1, 0xC9]),
+        ];
+        for (addr, bytes) in code.iter() {
+            for (i, b) in bytes.iter().enumerate() {
+                cpu.memory[addr + i as u16] = *b;
+            }
+        }
+        cpu
+    }
+
+    #[test]
+    fn test_step_over() {
+        let mut cpu = program();
+        let mode = RunMode::step_over(&cpu).unwrap();
+        assert_eq!(mode, RunMode::StepOver { addr: 0x0103, sp: 0xFFFE });
+        assert_eq!(run(&mut cpu, mode), 6);
+        assert_eq!(cpu.pc.value, 0x0103);
+        assert_eq!(RunMode::step_over(&cpu), None);
+    }
+
+    #[test]
+    fn test_step_out() {
+        let mut cpu = program();
+        cpu.step();
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.pc.value, 0xC011);
+        // PUSH/POP inside the frame don't count
+        let mode = RunMode::step_out(&cpu);
+        assert_eq!(run(&mut cpu, mode), 2);
+        assert_eq!(cpu.pc.value, 0xC003);
+        let mode = RunMode::step_out(&cpu);
+        assert_eq!(run(&mut cpu, mode), 1);
+        assert_eq!(cpu.pc.value, 0x0103);
+    }
+
+    #[test]
+    fn test_to_location() {
+        let mut cpu = program();
+        assert_eq!(run(&mut cpu, RunMode::ToLocation(Location::new(0, 0xC012))), 4);
+        assert_eq!(cpu.pc.value, 0xC012);
+    }
+}
@@ -0,0 +1,249 @@
+use crate::core::memory::Memory;
+
+use super::tiles::{BANK_TILES, tile_address};
+
+/*
+    Tilemaps
+
+    0x9800 and 0x9C00 each hold a 32x32 map of tile numbers, 256x256 pixels
+    in total. LCDC selects which one the background and the window use, and
+    the tile data addressing mode:
+        bit 3 - background map, 0x9800/0x9C00
+        bit 4 - tile data, 0x8800/0x8000 mode
+        bit 5 - window enable
+        bit 6 - window map, 0x9800/0x9C00
+
+    The screen shows 160x144 pixels of the background starting at SCX, SCY,
+    wrapping around the map edges. The window covers the screen from WX-7, WY.
+
+    On CGB VRAM bank 1 holds attributes of each map entry, at the same
+    address as the tile number in bank 0:
+        bits 0-2 - color palette
+        bit 3    - VRAM bank of the tile
+        bit 5    - horizontal flip
+        bit 6    - vertical flip
+        bit 7    - priority over sprites
+    Color palettes aren't emulated, pixels are shaded with BGP.
+*/
+
+pub const LCDC: usize = 0xFF40;
+pub const SCY: usize = 0xFF42;
+pub const SCX: usize = 0xFF43;
+pub const WY: usize = 0xFF4A;
+pub const WX: usize = 0xFF4B;
+
+pub const SCREEN_WIDTH: u8 = 160;
+pub const SCREEN_HEIGHT: u8 = 144;
+
+/// Part of the screen a map pixel is on the edge of
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overlay {
+    Viewport,
+    Window,
+}
+
+/// Map shown in the tilemap tab
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MapSelect {
+    /// Map LCDC selects for the background
+    #[default]
+    Background,
+    Map9800,
+    Map9C00,
+}
+
+/// CGB attributes of a map entry
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Attributes(pub u8);
+
+impl Attributes {
+    pub fn palette(self) -> u8 { self.0 & 0x07 }
+    pub fn bank(self) -> u8 { (self.0 >> 3) & 1 }
+    pub fn x_flip(self) -> bool { self.0 & 0x20 != 0 }
+    pub fn y_flip(self) -> bool { self.0 & 0x40 != 0 }
+    pub fn priority(self) -> bool { self.0 & 0x80 != 0 }
+}
+
+/// Tilemap tab state, `x` and `y` are map pixels shown in the top left corner
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MapView {
+    pub map: MapSelect,
+    pub x: u8,
+    pub y: u8,
+    /// Color tiles by their CGB palette instead of their pixels
+    pub attributes: bool,
+}
+
+impl MapView {
+    pub fn new() -> MapView {
+        MapView::default()
+    }
+
+    pub fn next_map(&mut self) {
+        self.map = match self.map {
+            MapSelect::Background => MapSelect::Map9800,
+            MapSelect::Map9800 => MapSelect::Map9C00,
+            MapSelect::Map9C00 => MapSelect::Background,
+        };
+    }
+
+    /// Scrolls by `dx`, `dy` pixels, wrapping around like the hardware does
+    pub fn scroll(&mut self, dx: i16, dy: i16) {
+        self.x = self.x.wrapping_add(dx as u8);
+        self.y = self.y.wrapping_add(dy as u8);
+    }
+
+    /// Moves the visible part to the screen viewport
+    pub fn center(&mut self, memory: &Memory) {
+        self.x = memory.peek(SCX);
+        self.y = memory.peek(SCY);
+    }
+
+    pub fn base(&self, memory: &Memory) -> u16 {
+        match self.map {
+            MapSelect::Background if memory.peek(LCDC) & 0x08 != 0 => 0x9C00,
+            MapSelect::Background | MapSelect::Map9800 => 0x9800,
+            MapSelect::Map9C00 => 0x9C00,
+        }
+    }
+}
+
+/// Index of the tile in VRAM number `n` refers to, with the current LCDC addressing mode
+pub fn tile_index(memory: &Memory, n: u8) -> usize {
+    if memory.peek(LCDC) & 0x10 != 0 || n >= 128 {
+        n as usize
+    } else {
+        256 + n as usize
+    }
+}
+
+/// Address of the map entry at `base` covering pixel `x`, `y`
+fn entry(base: u16, x: u8, y: u8) -> u16 {
+    base + (y as u16 / 8) * 32 + x as u16 / 8
+}
+
+/// Attributes of the map entry covering pixel `x`, `y`, all clear on DMG
+pub fn attributes(memory: &Memory, base: u16, x: u8, y: u8) -> Attributes {
+    match memory.is_cgb() {
+        true => Attributes(memory.peek_vram(1, entry(base, x, y))),
+        false => Attributes::default(),
+    }
+}
+
+/// Color number of pixel `x`, `y` of the map at `base`, with CGB attributes applied
+pub fn pixel(memory: &Memory, base: u16, x: u8, y: u8) -> u8 {
+    let attributes = attributes(memory, base, x, y);
+    let index = tile_index(memory, memory.peek_vram(0, entry(base, x, y)));
+    let (bank, tile) = tile_address(index + attributes.bank() as usize * BANK_TILES);
+    let (x, y) = (x % 8, y % 8);
+    let y = if attributes.y_flip() { 7 - y } else { y };
+    let bit = if attributes.x_flip() { x } else { 7 - x };
+    let row = tile + y as u16 * 2;
+    ((memory.peek_vram(bank, row + 1) >> bit) & 1) << 1 | ((memory.peek_vram(bank, row) >> bit) & 1)
+}
+
+/// Whether `offset` from the start of an edge `len` pixels long is on that edge
+fn on_edge(offset: u8, len: u8) -> bool {
+    offset == 0 || offset == len - 1
+}
+
+/// Rectangle edge pixel `x`, `y` of the map is on. The window is drawn
+/// inside the viewport when it's enabled and on screen
+pub fn overlay(memory: &Memory, x: u8, y: u8) -> Option<Overlay> {
+    // offsets from the top left corner of the screen, wrapped like the map
+    let sx = x.wrapping_sub(memory.peek(SCX));
+    let sy = y.wrapping_sub(memory.peek(SCY));
+    if sx >= SCREEN_WIDTH || sy >= SCREEN_HEIGHT {
+        return None;
+    }
+    let (wx, wy) = (memory.peek(WX).saturating_sub(7), memory.peek(WY));
+    let window = memory.peek(LCDC) & 0x20 != 0 && wx < SCREEN_WIDTH && wy < SCREEN_HEIGHT;
+    if window && sx >= wx && sy >= wy && (sx == wx || sy == wy) {
+        return Some(Overlay::Window);
+    }
+    if on_edge(sx, SCREEN_WIDTH) || on_edge(sy, SCREEN_HEIGHT) {
+        return Some(Overlay::Viewport);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_addressing() {
+        let mut mem = Memory::new();
+        mem[LCDC] = 0x91;
+        assert_eq!(tile_index(&mem, 0x01), 1);
+        assert_eq!(tile_index(&mem, 0x81), 0x81);
+        mem[LCDC] = 0x81;
+        assert_eq!(tile_index(&mem, 0x01), 257);
+        assert_eq!(tile_index(&mem, 0x81), 0x81);
+
+        let mut view = MapView::new();
+        assert_eq!(view.base(&mem), 0x9800);
+        mem[LCDC] = 0x89;
+        assert_eq!(view.base(&mem), 0x9C00);
+        view.next_map();
+        assert_eq!(view.base(&mem), 0x9800);
+    }
+
+    #[test]
+    fn test_pixel() {
+        let mut mem = Memory::new();
+        mem[LCDC] = 0x91;
+        // second entry of the second row is tile 2, its row 3 is 0x80 0x80
+        mem[0x9821u16] = 2;
+        mem[0x8026u16] = 0x80;
+        mem[0x8027u16] = 0x80;
+        assert_eq!(pixel(&mem, 0x9800, 8, 11), 3);
+        assert_eq!(pixel(&mem, 0x9800, 9, 11), 0);
+        assert_eq!(pixel(&mem, 0x9C00, 8, 11), 0);
+    }
+
+    #[test]
+    fn test_attributes() {
+        let mut mem = Memory::new();
+        let mut rom = vec![0; 0x8000];
+        rom[0x0143] = 0x80;
+        mem.load_rom(rom);
+        mem[LCDC] = 0x91;
+        // first entry is tile 1 from bank 1, flipped both ways, palette 5
+        mem.write(0x9800, 1);
+        mem.write(0xFF4F, 1);
+        mem.write(0x9800, 0x6D);
+        mem.write(0x801E, 0x01);
+        mem.write(0xFF4F, 0);
+        let first = attributes(&mem, 0x9800, 3, 3);
+        assert_eq!(first.palette(), 5);
+        assert_eq!(first.bank(), 1);
+        assert!(first.x_flip() && first.y_flip() && !first.priority());
+        // pixel 7,7 of the tile is drawn at 0,0
+        assert_eq!(pixel(&mem, 0x9800, 0, 0), 1);
+        assert_eq!(pixel(&mem, 0x9800, 7, 7), 0);
+        assert_eq!(attributes(&mem, 0x9800, 8, 0), Attributes(0));
+    }
+
+    #[test]
+    fn test_overlay() {
+        let mut mem = Memory::new();
+        mem[SCX] = 200;
+        mem[SCY] = 0;
+        assert_eq!(overlay(&mem, 200, 0), Some(Overlay::Viewport));
+        assert_eq!(overlay(&mem, 201, 1), None);
+        // right edge wraps around the map
+        assert_eq!(overlay(&mem, 103, 50), Some(Overlay::Viewport));
+        assert_eq!(overlay(&mem, 104, 50), None);
+        assert_eq!(overlay(&mem, 210, 143), Some(Overlay::Viewport));
+
+        mem[LCDC] = 0xA0;
+        mem[WX] = 17;
+        mem[WY] = 100;
+        assert_eq!(overlay(&mem, 210, 120), Some(Overlay::Window));
+        assert_eq!(overlay(&mem, 220, 100), Some(Overlay::Window));
+        assert_eq!(overlay(&mem, 209, 120), None);
+        mem[WY] = 144;
+        assert_eq!(overlay(&mem, 210, 120), None);
+    }
+}
@@ -0,0 +1,182 @@
+use std::fmt;
+
+use crate::core::memory::Memory;
+
+/*
+    Tile data
+
+    0x8000-0x97FF holds 384 tiles of 8x8 pixels, 16 bytes each. Every row is
+    two bytes: the first one has low bits of the 8 pixels, the second one
+    high bits, leftmost pixel in bit 7.
+
+    Tilemaps and sprites refer to tiles by a byte number, resolved with one of
+    two addressing modes selected by LCDC bit 4:
+        0x8000 mode - unsigned number from 0x8000, tiles 0-255
+        0x8800 mode - signed number from 0x9000, tiles 128-383
+
+    CGB cartridges get a second VRAM bank with 384 more tiles, shown after
+    the first bank as tiles 384-767. Tile numbers refer to either bank, the
+    map attribute or sprite flags pick one.
+*/
+
+/// Tiles in a single VRAM bank
+pub const BANK_TILES: usize = 384;
+pub const TILES_PER_ROW: usize = 16;
+const TILE_DATA: u16 = 0x8000;
+const TILE_SIZE: u16 = 16;
+
+/// Palette tiles are drawn with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TilePalette {
+    /// Color numbers as they are stored
+    Raw,
+    /// Background palette
+    #[default]
+    Bgp,
+    /// Object palettes
+    Obp0,
+    Obp1,
+}
+
+impl TilePalette {
+    pub fn next(self) -> TilePalette {
+        match self {
+            TilePalette::Raw => TilePalette::Bgp,
+            TilePalette::Bgp => TilePalette::Obp0,
+            TilePalette::Obp0 => TilePalette::Obp1,
+            TilePalette::Obp1 => TilePalette::Raw,
+        }
+    }
+
+    /// Shade from white(0) to black(3) of color number `color`
+    pub fn shade(self, memory: &Memory, color: u8) -> u8 {
+        let register = match self {
+            TilePalette::Raw => return color,
+            TilePalette::Bgp => 0xFF47,
+            TilePalette::Obp0 => 0xFF48,
+            TilePalette::Obp1 => 0xFF49,
+        };
+        (memory.peek(register) >> (color * 2)) & 0b11
+    }
+}
+
+impl fmt::Display for TilePalette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TilePalette::Raw => write!(f, "raw"),
+            TilePalette::Bgp => write!(f, "BGP"),
+            TilePalette::Obp0 => write!(f, "OBP0"),
+            TilePalette::Obp1 => write!(f, "OBP1"),
+        }
+    }
+}
+
+/// Tiles in VRAM, both banks on CGB
+pub fn tile_count(memory: &Memory) -> usize {
+    if memory.is_cgb() { BANK_TILES * 2 } else { BANK_TILES }
+}
+
+/// VRAM bank and address of tile `index`
+pub fn tile_address(index: usize) -> (u8, u16) {
+    ((index / BANK_TILES) as u8, TILE_DATA + (index % BANK_TILES) as u16 * TILE_SIZE)
+}
+
+/// Color numbers of tile `index`, row by row
+pub fn decode_tile(memory: &Memory, index: usize) -> [[u8; 8]; 8] {
+    let mut pixels = [[0; 8]; 8];
+    let (bank, addr) = tile_address(index);
+    for (y, row) in pixels.iter_mut().enumerate() {
+        let low = memory.peek_vram(bank, addr + y as u16 * 2);
+        let high = memory.peek_vram(bank, addr + y as u16 * 2 + 1);
+        for (x, pixel) in row.iter_mut().enumerate() {
+            let bit = 7 - x;
+            *pixel = ((high >> bit) & 1) << 1 | ((low >> bit) & 1);
+        }
+    }
+    pixels
+}
+
+/// Tile numbers that refer to tile `index` in 0x8000 and 0x8800 addressing modes
+pub fn tile_numbers(index: usize) -> (Option<u8>, Option<u8>) {
+    let index = index % BANK_TILES;
+    let unsigned = if index < 256 { Some(index as u8) } else { None };
+    let signed = if index >= 128 { Some((index % 256) as u8) } else { None };
+    (unsigned, signed)
+}
+
+/// Selected tile and palette of the VRAM tab
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TileView {
+    pub selected: usize,
+    pub palette: TilePalette,
+}
+
+impl TileView {
+    pub fn new() -> TileView {
+        TileView::default()
+    }
+
+    /// Moves selection by `delta` tiles, stopping at the first and the last of `count`
+    pub fn move_by(&mut self, delta: i32, count: usize) {
+        self.selected = (self.selected as i32 + delta).clamp(0, count as i32 - 1) as usize;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let mut mem = Memory::new();
+        // first row of tile 1: colors 0 1 2 3 0 1 2 3
+        mem[0x8010u16] = 0b0101_0101;
+        mem[0x8011u16] = 0b0011_0011;
+        assert_eq!(decode_tile(&mem, 1)[0], [0, 1, 2, 3, 0, 1, 2, 3]);
+        assert_eq!(decode_tile(&mem, 1)[1], [0; 8]);
+
+        mem[0xFF47u16] = 0b1110_0100;
+        mem[0xFF48u16] = 0b0001_1011;
+        assert_eq!(TilePalette::Bgp.shade(&mem, 1), 1);
+        assert_eq!(TilePalette::Obp0.shade(&mem, 1), 2);
+        assert_eq!(TilePalette::Raw.shade(&mem, 3), 3);
+    }
+
+    #[test]
+    fn test_cgb_banks() {
+        let mut mem = Memory::new();
+        let mut rom = vec![0; 0x8000];
+        rom[0x0143] = 0xC0;
+        mem.load_rom(rom);
+        assert_eq!(tile_count(&mem), 768);
+        // first row of tile 1 in bank 1
+        mem.write(0xFF4F, 0x01);
+        mem.write(0x8010, 0xFF);
+        mem.write(0xFF4F, 0x00);
+        assert_eq!(decode_tile(&mem, 1)[0], [0; 8]);
+        assert_eq!(decode_tile(&mem, BANK_TILES + 1)[0], [1; 8]);
+        assert_eq!(tile_numbers(BANK_TILES + 1), (Some(1), None));
+
+        assert_eq!(tile_count(&Memory::new()), 384);
+    }
+
+    #[test]
+    fn test_addressing() {
+        assert_eq!(tile_address(0), (0, 0x8000));
+        assert_eq!(tile_address(383), (0, 0x97F0));
+        assert_eq!(tile_address(384), (1, 0x8000));
+        assert_eq!(tile_numbers(0), (Some(0), None));
+        assert_eq!(tile_numbers(128), (Some(128), Some(128)));
+        assert_eq!(tile_numbers(255), (Some(255), Some(255)));
+        assert_eq!(tile_numbers(256), (None, Some(0)));
+        assert_eq!(tile_numbers(383), (None, Some(127)));
+
+        let mut view = TileView::new();
+        view.move_by(-1, 384);
+        assert_eq!(view.selected, 0);
+        view.move_by(1000, 384);
+        assert_eq!(view.selected, 383);
+        view.move_by(1000, 768);
+        assert_eq!(view.selected, 767);
+    }
+}
@@ -1,13 +1,26 @@
-use indexmap::IndexMap;
-use tui::{widgets::{Paragraph, Block, Borders, BorderType, List, ListItem, canvas::{Canvas, Line, MapResolution, Map, Rectangle, Context, Points}, Widget}, text::{Spans, Span}, style::{Style, Color, Modifier}, layout::{Alignment, Rect}, backend::Backend, Frame, symbols::Marker};
+use std::collections::HashMap;
 
-use crate::cpu::{CPU, Flag};
+use tui::{widgets::{Paragraph, Block, Borders, BorderType, List, ListItem, ListState, Widget}, text::{Spans, Span}, style::{Style, Color, Modifier}, layout::{Alignment, Constraint, Direction, Layout, Rect}, backend::Backend, buffer::Buffer, Frame};
+
+use crate::{cpu::{CPU, Flag}, core::{callstack::is_return_address, memory::Memory, watchpoints::{Watchpoint, Watchpoints}}, disasm::{Listing, Location, symbols::Symbols}};
+
+use super::{
+    breakpoints::{Breakpoints, Condition},
+    console::{Console, LogLine},
+    hexview::{HexView, BYTES_PER_ROW, REGIONS},
+    oam::{self, Sprite, SPRITE_COUNT},
+    tilemap::{self, MapView, Overlay},
+    tiles::{TilePalette, TileView, TILES_PER_ROW, decode_tile, tile_address, tile_count, tile_numbers},
+};
+
+/// Maximum amount of 16bit entries shown in the stack view
+const STACK_VIEW_SIZE: u16 = 32;
 
 #[allow(overflowing_literals)]
 pub fn render_cpu<'a>(
-    disas: &'a IndexMap<u16, String>, asm_height: u16, cpu: &CPU, 
+    disas: &'a Listing, symbols: &Symbols, breakpoints: &Breakpoints, cursor: Option<u16>, asm_height: u16, cpu: &CPU,
 ) -> ((List<'a>, usize), Paragraph<'a>, Paragraph<'a>) {
-    let current: usize = asm_height as usize / 2;
+    let symbol = |addr| symbols.name(Location::mapped(&cpu.memory, addr));
 
     let asm = Block::default()
         .borders(Borders::ALL)
@@ -25,23 +38,31 @@ pub fn render_cpu<'a>(
         .title("Stack")
         .border_type(BorderType::Plain);
 
-    let pc_index = disas.get_index_of(&cpu.pc.value).unwrap();
-    let range = (pc_index - (asm_height as usize / 2))..(pc_index + (asm_height as usize / 2));
-    
-    let disas_iter = disas
-        .iter()
-        .enumerate()
-        .skip_while(|i| !range.contains(&i.0))
-        .take(asm_height as usize);
-
-    let disas_spans: Vec<ListItem> = disas_iter
-        .map(|t| {
-            let (addr, inst) = t.1.1.split_once(' ').unwrap();
-            ListItem::new(Spans::from(vec![
-                Span::styled(addr, Style::default().fg(Color::DarkGray)),
+    let selected = disas.index_of(cursor.unwrap_or(cpu.pc.value)).unwrap_or(0);
+    let start = selected.saturating_sub(asm_height as usize / 2);
+    let current = selected - start;
+
+    let disas_spans: Vec<ListItem> = disas.lines
+        .values()
+        .skip(start)
+        .take(asm_height as usize)
+        .map(|line| {
+            let style = if line.is_code() { Style::default() } else { Style::default().fg(Color::Gray) };
+            let mut spans = Vec::new();
+            if let Some(label) = symbols.name(line.location) {
+                spans.push(Spans::from(Span::styled(format!("{}:", label), Style::default().fg(Color::LightGreen))));
+            }
+            spans.push(Spans::from(vec![
+                Span::styled(
+                    if breakpoints.contains(line.location) { "●" } else { " " },
+                    Style::default().fg(Color::Red),
+                ),
+                Span::raw(if line.location.addr == cpu.pc.value { ">" } else { " " }),
+                Span::styled(format!("${:04X}", line.location.addr), Style::default().fg(Color::DarkGray)),
                 Span::raw("       "),
-                Span::styled(inst, Style::default()),
-            ]))
+                Span::styled(line.text_with(symbol), style),
+            ]));
+            ListItem::new(spans)
         })
         .collect();
 
@@ -56,6 +77,7 @@ pub fn render_cpu<'a>(
     let freqs = [1024, 16, 64, 256];
     let freq_idx = cpu.memory.tac() & 3;
     let freq = freqs[freq_idx as usize];
+    let next = cpu.instruction_at(cpu.pc.value);
     let p1 = Paragraph::new(vec![
         Spans::from(vec![
             Span::raw(format!("PC ${:04X}", cpu.pc.value)),
@@ -109,6 +131,20 @@ pub fn render_cpu<'a>(
             Span::raw(" "),
             Span::styled("H", Style::default().fg(if cpu.get_flag(Flag::H) { Color::Green } else { Color::LightRed })),
         ]),
+        Spans::from(vec![
+            Span::styled("Next", Style::default().fg(Color::DarkGray)),
+            Span::raw(format!(
+                " {} bytes     {} cycles     flags read {} written {}",
+                next.length,
+                if next.is_conditional() {
+                    format!("{}/{}", next.cycles, next.cycles_taken)
+                } else {
+                    next.cycles.to_string()
+                },
+                next.flags_read,
+                next.flags_written,
+            )),
+        ]),
         Spans::from(vec![
             Span::raw("Timers: "),
             Span::styled(
@@ -130,9 +166,33 @@ pub fn render_cpu<'a>(
     ])
         .alignment(Alignment::Left)
         .block(registers);
-    let p2 = Paragraph::new(Spans::from(vec![
-        Span::raw(format!("SP ${:04X}", cpu.stack_pointer.value)),
-    ]))
+    let sp = cpu.stack_pointer.value;
+    let origin = cpu.call_stack.origin;
+    let mut stack_lines = vec![Spans::from(Span::raw(format!("SP ${:04X}  origin ${:04X}", sp, origin)))];
+    // words down to the origin, or a few if SP is outside of the stack
+    let words = if sp <= origin { ((origin - sp) / 2).min(STACK_VIEW_SIZE) } else { 8 };
+    for i in 0..words {
+        let addr = sp.wrapping_add(i * 2);
+        let value = u16::from_le_bytes([
+            cpu.memory.peek(addr as usize),
+            cpu.memory.peek(addr.wrapping_add(1) as usize),
+        ]);
+        let mut spans = vec![
+            Span::styled(format!("${:04X}", addr), Style::default().fg(Color::DarkGray)),
+            Span::styled(format!(" ${:04X}", value), Style::default().fg(Color::LightYellow)),
+        ];
+        if is_return_address(&cpu.memory, value) {
+            spans.push(Span::styled(" ret", Style::default().fg(Color::LightMagenta)));
+            spans.push(Span::styled(
+                format!(" {}", describe(symbols, &cpu.memory, value)),
+                Style::default().fg(Color::LightGreen),
+            ));
+        } else if let Some(name) = symbol(value) {
+            spans.push(Span::styled(format!(" {}", name), Style::default().fg(Color::LightGreen)));
+        }
+        stack_lines.push(Spans::from(spans));
+    }
+    let p2 = Paragraph::new(stack_lines)
         .alignment(Alignment::Left)
         .block(stack);
     
@@ -164,16 +224,139 @@ pub fn render_cpu<'a>(
     ((list, current), p1, p2)
 }
 
-pub fn build_cpu_controls<'a>() -> Paragraph<'a> {
+/// List of breakpoints with their labels, followed by watchpoints
+/// Address with the label it's in, e.g. `$0155 Main+$5`
+pub fn describe(symbols: &Symbols, memory: &Memory, addr: u16) -> String {
+    match symbols.containing(Location::mapped(memory, addr)) {
+        Some((name, 0)) => format!("${:04X} {}", addr, name),
+        Some((name, offset)) => format!("${:04X} {}+${:X}", addr, name, offset),
+        None => format!("${:04X}", addr),
+    }
+}
+
+/// Backtrace from the shadow call stack, innermost call first
+pub fn render_call_stack<'a>(cpu: &CPU, symbols: &Symbols) -> Paragraph<'a> {
+    let mut lines = vec![Spans::from(vec![
+        Span::styled("#0 ", Style::default().fg(Color::DarkGray)),
+        Span::styled(describe(symbols, &cpu.memory, cpu.pc.value), Style::default().fg(Color::LightGreen)),
+    ])];
+    lines.extend(cpu.call_stack.iter().enumerate().map(|(i, frame)| Spans::from(vec![
+        Span::styled(format!("#{} ", i + 1), Style::default().fg(Color::DarkGray)),
+        Span::styled(describe(symbols, &cpu.memory, frame.origin), Style::default().fg(Color::LightGreen)),
+        Span::styled(
+            format!(" {} ${:04X}", frame.kind, frame.target),
+            Style::default().fg(Color::DarkGray),
+        ),
+    ])));
+    Paragraph::new(lines)
+        .alignment(Alignment::Left)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(format!("Call stack ({})", cpu.call_stack.depth()))
+                .border_type(BorderType::Plain),
+        )
+}
+
+pub fn render_breakpoints<'a>(
+    breakpoints: &Breakpoints,
+    watchpoints: &Watchpoints,
+    watch_conditions: &HashMap<Watchpoint, Condition>,
+    symbols: &Symbols,
+) -> Paragraph<'a> {
+    let condition_style = Style::default().fg(Color::Yellow);
+    let mut lines: Vec<Spans> = breakpoints
+        .iter()
+        .map(|(location, condition)| Spans::from(vec![
+            Span::styled("● ", Style::default().fg(Color::Red)),
+            Span::raw(location.to_string()),
+            Span::styled(
+                symbols.name(*location).map(|name| format!(" {}", name)).unwrap_or_default(),
+                Style::default().fg(Color::LightGreen),
+            ),
+            Span::styled(condition.to_string(), condition_style),
+        ]))
+        .collect();
+    lines.extend(watchpoints.iter().map(|w| Spans::from(vec![
+        Span::styled("◆ ", Style::default().fg(Color::LightMagenta)),
+        Span::raw(format!("${:04X}-${:04X} {}", w.range.start(), w.range.end(), w.kind)),
+        Span::styled(
+            watch_conditions.get(w).map(Condition::to_string).unwrap_or_default(),
+            condition_style,
+        ),
+    ])));
+    Paragraph::new(lines)
+        .alignment(Alignment::Left)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title("Breakpoints / Watchpoints")
+                .border_type(BorderType::Plain),
+        )
+}
+
+pub fn build_cpu_controls<'a>(running: bool, status: Option<&str>) -> Paragraph<'a> {
     let btn_style = Style::default().fg(Color::LightBlue).add_modifier(Modifier::BOLD);
+    let title = match status {
+        Some(status) => format!("Controls - {}", status),
+        None if running => "Controls - running".to_string(),
+        None => "Controls".to_string(),
+    };
     let p = Paragraph::new(vec![
         Spans::from(vec![
             Span::styled("[Space]", btn_style),
             Span::raw(" - next instruction"),
             Span::raw("   "),
+            Span::styled("[G]", btn_style),
+            Span::raw(if running { " - pause" } else { " - run" }),
+            Span::raw("   "),
+            Span::styled("[N]", btn_style),
+            Span::raw(" - step over"),
+            Span::raw("   "),
+            Span::styled("[U]", btn_style),
+            Span::raw(" - step out"),
+            Span::raw("   "),
+            Span::styled("[H]", btn_style),
+            Span::raw(" - run to cursor"),
+            Span::raw("   "),
+            Span::styled("[B]", btn_style),
+            Span::raw(" - toggle breakpoint"),
+            Span::raw("   "),
+            Span::styled("[Shift+B]", btn_style),
+            Span::raw(" - breakpoint at"),
+            Span::raw("   "),
+            Span::styled("[W]", btn_style),
+            Span::raw(" - watchpoint"),
+            Span::raw("   "),
             Span::styled("[R]", btn_style),
             Span::raw(" - reset"),
             Span::raw("   "),
+            Span::styled("[Shift+R]", btn_style),
+            Span::raw(" - reload ROM"),
+            Span::raw("   "),
+            Span::styled("[Z/Shift+Z]", btn_style),
+            Span::raw(" - rewind step/frame"),
+            Span::raw("   "),
+            Span::styled("[Shift+G]", btn_style),
+            Span::raw(" - run backward"),
+            Span::raw("   "),
+            Span::styled("[F5/F9]", btn_style),
+            Span::raw(" - save/load state"),
+            Span::raw("   "),
+            Span::styled("[E]", btn_style),
+            Span::raw(" - export asm"),
+            Span::raw("   "),
+            Span::styled("[A]", btn_style),
+            Span::raw(" - patch instruction"),
+            Span::raw("   "),
+            Span::styled("[P]", btn_style),
+            Span::raw(" - print expression"),
+            Span::raw("   "),
+            Span::styled("[:]", btn_style),
+            Span::raw(" - console"),
+            Span::raw("   "),
         ])
     ])
         .style(Style::default().fg(Color::LightCyan))
@@ -182,38 +365,353 @@ pub fn build_cpu_controls<'a>() -> Paragraph<'a> {
             Block::default()
                 .borders(Borders::ALL)
                 .style(Style::default().fg(Color::White))
-                .title("Controls")
+                .title(title)
                 .border_type(BorderType::Plain),
         );
     p
 }
 
-pub fn build_vram<'a, B: Backend>(cpu: &CPU, chunk: &Rect, be: &mut Frame<B>){
-    let canvas = Canvas::default()
-        .block(Block::default().title("VRAM").borders(Borders::ALL)).marker(Marker::Braille)
-        // 19x21 = 152x168
-        .x_bounds([0., 152.0])
-        .y_bounds([0., 168.0])
-        .paint(|ctx| {
-            let colors = [Color::White, Color::Gray, Color::DarkGray, Color::Black];
-            let mut base = 0x8000;
-            let (mut x, y) = (152.0, 168.0);
-
-            for j in 0..8 {
-                let lower = cpu.memory.read(base);
-                base += 1;
-                let upper = cpu.memory.read(base);
-                for i in 0..8 {
-                    // C3 => 11000011
-                    // 20 => 00100000
-                    let c = 7 - i;
-                    let color_idx = (((upper >> c) & 1) << 1) | ((lower >> c) & 1);
-                    ctx.draw(&Points {
-                        coords: &[(x + i as f64, y + j as f64)],
-                        color: colors[color_idx as usize],
-                    });
+/// Console output followed by the input line, scrolled to the last lines that fit in `height`
+pub fn build_console<'a>(console: &Console, focused: bool, height: u16) -> Paragraph<'a> {
+    let visible = (height as usize).saturating_sub(3);
+    let log = console.lines();
+    let mut lines: Vec<Spans> = log[log.len().saturating_sub(visible)..]
+        .iter()
+        .map(|line| match line {
+            LogLine::Input(text) => Spans::from(Span::styled(format!("> {}", text), Style::default().fg(Color::LightBlue))),
+            LogLine::Output(text) => Spans::from(Span::raw(text.clone())),
+            LogLine::Error(text) => Spans::from(Span::styled(text.clone(), Style::default().fg(Color::Red))),
+        })
+        .collect();
+    let mut input = vec![
+        Span::styled("> ", Style::default().fg(Color::LightBlue).add_modifier(Modifier::BOLD)),
+        Span::raw(console.input.clone()),
+    ];
+    if focused {
+        input.push(Span::styled("_", Style::default().add_modifier(Modifier::SLOW_BLINK)));
+    }
+    lines.push(Spans::from(input));
+    let title = if focused {
+        "Console [Enter] - run, [Tab] - complete, [Up/Down] - history, [Esc] - close"
+    } else {
+        "Console [:] - open"
+    };
+    Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(title)
+                .border_type(BorderType::Plain),
+        )
+}
+
+/// Hex and ASCII dump of the rows around the selected byte
+pub fn build_memory<'a>(view: &HexView, memory: &Memory, height: u16) -> Paragraph<'a> {
+    let rows = height.saturating_sub(2);
+    let first = view.first_row(rows) as u32;
+    let lines: Vec<Spans> = (0..rows as u32)
+        .map(|row| first + row * BYTES_PER_ROW as u32)
+        .take_while(|addr| *addr <= 0xFFFF)
+        .map(|row_addr| {
+            let mut spans = vec![Span::styled(format!("${:04X}  ", row_addr), Style::default().fg(Color::DarkGray))];
+            let mut ascii = String::new();
+            for addr in (row_addr..row_addr + BYTES_PER_ROW as u32).map(|a| a as u16) {
+                let value = memory.peek(addr as usize);
+                let mut style = Style::default();
+                if view.is_changed(memory, addr) {
+                    style = style.fg(Color::Yellow);
                 }
+                let text = match view.pending() {
+                    Some(high) if addr == view.cursor => format!("{:X}_", high),
+                    _ => format!("{:02X}", value),
+                };
+                if addr == view.cursor {
+                    style = style.add_modifier(Modifier::REVERSED);
+                    if view.editing {
+                        style = style.fg(Color::LightRed);
+                    }
+                }
+                spans.push(Span::styled(text, style));
+                spans.push(Span::raw(if addr % 8 == 7 { "  " } else { " " }));
+                ascii.push(if value.is_ascii_graphic() { value as char } else { '.' });
+            }
+            spans.push(Span::styled(ascii, Style::default().fg(Color::Gray)));
+            Spans::from(spans)
+        })
+        .collect();
+    let regions: Vec<String> = REGIONS.iter().enumerate().map(|(i, (name, _))| format!("[{}] {}", i + 1, name)).collect();
+    let title = if view.editing {
+        format!("Memory ${:04X} - editing, [0-F] - type byte, [Enter]/[Esc] - stop", view.cursor)
+    } else {
+        format!("Memory ${:04X} [PgUp/PgDn] - page, [Enter] - edit, {}", view.cursor, regions.join(" "))
+    };
+    Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(title)
+                .border_type(BorderType::Plain),
+        )
+}
+
+/// Terminal colors of shades from white to black
+const SHADES: [Color; 4] = [Color::White, Color::Gray, Color::DarkGray, Color::Black];
+
+/// Shades the selected tile is drawn with
+const SELECTED_SHADES: [Color; 4] = [Color::LightYellow, Color::Yellow, Color::LightRed, Color::Red];
+
+/// Grid of all tiles in VRAM, every cell shows two pixels above each other with a half block
+struct TileGrid<'a> {
+    view: &'a TileView,
+    memory: &'a Memory,
+}
+
+impl Widget for TileGrid<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        // 8 pixel rows of a tile take 4 lines
+        let visible_rows = (area.height / 4) as usize;
+        let total_rows = tile_count(self.memory) / TILES_PER_ROW;
+        let selected_row = self.view.selected / TILES_PER_ROW;
+        let first_row = selected_row.saturating_sub(visible_rows / 2).min(total_rows.saturating_sub(visible_rows));
+
+        for (row, tile_row) in (first_row..total_rows).take(visible_rows).enumerate() {
+            for column in 0..TILES_PER_ROW {
+                let index = tile_row * TILES_PER_ROW + column;
+                let shades = if index == self.view.selected { SELECTED_SHADES } else { SHADES };
+                let pixels = decode_tile(self.memory, index);
+                for (y, pair) in pixels.chunks(2).enumerate() {
+                    for (x, (top, bottom)) in pair[0].iter().zip(pair[1].iter()).enumerate() {
+                        let (cell_x, cell_y) = (area.x + (column * 8 + x) as u16, area.y + (row * 4 + y) as u16);
+                        if cell_x >= area.right() || cell_y >= area.bottom() {
+                            continue;
+                        }
+                        let top = self.view.palette.shade(self.memory, *top);
+                        let bottom = self.view.palette.shade(self.memory, *bottom);
+                        buf.get_mut(cell_x, cell_y)
+                            .set_symbol("▀")
+                            .set_fg(shades[top as usize])
+                            .set_bg(shades[bottom as usize]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Selected tile enlarged, with its address and the tile numbers that refer to it
+fn build_tile_info<'a>(view: &TileView, memory: &Memory) -> Paragraph<'a> {
+    let index = view.selected;
+    let mut lines: Vec<Spans> = decode_tile(memory, index)
+        .iter()
+        .map(|row| Spans::from(
+            row.iter()
+                .map(|c| Span::styled("██", Style::default().fg(SHADES[view.palette.shade(memory, *c) as usize])))
+                .collect::<Vec<_>>()
+        ))
+        .collect();
+    let (unsigned, signed) = tile_numbers(index);
+    let number = |mode: &str, n: Option<u8>| match n {
+        Some(n) => format!("{} mode: ${:02X}", mode, n),
+        None => format!("{} mode: -", mode),
+    };
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(format!("Tile {} (${:03X})", index, index)));
+    let (bank, addr) = tile_address(index);
+    lines.push(Spans::from(match memory.is_cgb() {
+        true => format!("Address ${:04X}, bank {}", addr, bank),
+        false => format!("Address ${:04X}", addr),
+    }));
+    lines.push(Spans::from(number("$8000", unsigned)));
+    lines.push(Spans::from(number("$8800", signed)));
+    lines.push(Spans::from(format!("Palette {}", view.palette)));
+    Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title("Tile")
+                .border_type(BorderType::Plain),
+        )
+}
+
+pub fn build_vram<B: Backend>(view: &TileView, memory: &Memory, chunk: &Rect, be: &mut Frame<B>) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(TILES_PER_ROW as u16 * 8 + 2), Constraint::Min(20)].as_ref())
+        .split(*chunk);
+    let block = Block::default()
+        .title("VRAM [Arrows] - select tile, [Tab] - palette")
+        .borders(Borders::ALL);
+    let grid = block.inner(chunks[0]);
+    be.render_widget(block, chunks[0]);
+    be.render_widget(TileGrid { view, memory }, grid);
+    be.render_widget(build_tile_info(view, memory), chunks[1]);
+}
+
+/// Colors of CGB palettes 0-7 when tiles are colored by attributes
+const PALETTE_COLORS: [Color; 8] = [
+    Color::White, Color::LightRed, Color::LightGreen, Color::LightYellow,
+    Color::LightBlue, Color::LightMagenta, Color::LightCyan, Color::Gray,
+];
+
+/// Part of a tilemap from the view position, two pixels per cell like `TileGrid`
+struct TileMap<'a> {
+    view: &'a MapView,
+    memory: &'a Memory,
+}
+
+impl Widget for TileMap<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let base = self.view.base(self.memory);
+        let color = |x: u8, y: u8| match tilemap::overlay(self.memory, x, y) {
+            Some(Overlay::Viewport) => Color::Red,
+            Some(Overlay::Window) => Color::LightMagenta,
+            None if self.view.attributes && self.memory.is_cgb() => {
+                PALETTE_COLORS[tilemap::attributes(self.memory, base, x, y).palette() as usize]
+            },
+            None => SHADES[TilePalette::Bgp.shade(self.memory, tilemap::pixel(self.memory, base, x, y)) as usize],
+        };
+        // the whole map fits in 256 columns and 128 lines
+        for cell_y in 0..area.height.min(128) {
+            for cell_x in 0..area.width.min(256) {
+                let x = self.view.x.wrapping_add(cell_x as u8);
+                let y = self.view.y.wrapping_add(cell_y as u8 * 2);
+                buf.get_mut(area.x + cell_x, area.y + cell_y)
+                    .set_symbol("▀")
+                    .set_fg(color(x, y))
+                    .set_bg(color(x, y.wrapping_add(1)));
+            }
+        }
+    }
+}
+
+pub fn build_tilemap<B: Backend>(view: &MapView, memory: &Memory, chunk: &Rect, be: &mut Frame<B>) {
+    // attributes of the tile in the top left corner
+    let cgb = match memory.is_cgb() {
+        true => {
+            let a = tilemap::attributes(memory, view.base(memory), view.x, view.y);
+            format!(
+                " - palette {} bank {}{}{}{}, [Shift+A] - {}",
+                a.palette(), a.bank(),
+                if a.x_flip() { " X flip" } else { "" },
+                if a.y_flip() { " Y flip" } else { "" },
+                if a.priority() { " priority" } else { "" },
+                if view.attributes { "pixels" } else { "palettes" },
+            )
+        },
+        false => String::new(),
+    };
+    let block = Block::default()
+        .title(format!(
+            "Tilemap ${:04X} at {},{}{} - SCX {} SCY {} WX {} WY {} [Arrows] - scroll, [Enter] - to screen, [Tab] - map",
+            view.base(memory), view.x, view.y, cgb,
+            memory.peek(tilemap::SCX), memory.peek(tilemap::SCY), memory.peek(tilemap::WX), memory.peek(tilemap::WY),
+        ))
+        .borders(Borders::ALL);
+    let area = block.inner(*chunk);
+    be.render_widget(block, *chunk);
+    be.render_widget(TileMap { view, memory }, area);
+}
+
+/// Sprites per row of the OAM preview grid
+const SPRITE_GRID_COLUMNS: usize = 8;
+
+/// Previews of all sprites with their OAM numbers, transparent pixels are left blank
+struct SpriteGrid<'a> {
+    memory: &'a Memory,
+    selected: usize,
+}
+
+impl Widget for SpriteGrid<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let height = oam::sprite_height(self.memory);
+        // number line and pixels, two rows per line
+        let cell_height = 1 + height as u16 / 2;
+        for index in 0..SPRITE_COUNT {
+            let x = area.x + (index % SPRITE_GRID_COLUMNS) as u16 * 10;
+            let y = area.y + (index / SPRITE_GRID_COLUMNS) as u16 * (cell_height + 1);
+            if x + 8 > area.right() || y + cell_height > area.bottom() {
+                continue;
             }
-        });
-        be.render_widget(canvas, *chunk)
-}
\ No newline at end of file
+            let sprite = Sprite::read(self.memory, index);
+            let style = if index == self.selected {
+                Style::default().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default()
+            };
+            buf.set_string(x, y, format!("{:>2}", index), style);
+            let color = |c: u8| match c {
+                0 => Color::Reset,
+                c => SHADES[sprite.palette().shade(self.memory, c) as usize],
+            };
+            for (line, rows) in sprite.pixels(self.memory, height).chunks(2).enumerate() {
+                for (column, (top, bottom)) in rows[0].iter().zip(rows[1].iter()).enumerate() {
+                    buf.get_mut(x + column as u16, y + 1 + line as u16)
+                        .set_symbol("▀")
+                        .set_fg(color(*top))
+                        .set_bg(color(*bottom));
+                }
+            }
+        }
+    }
+}
+
+/// Table of OAM entries next to sprite previews. Sprites that are off screen are
+/// dimmed, the ones over the limit of the current line are red
+pub fn build_oam<B: Backend>(selected: usize, memory: &Memory, chunk: &Rect, be: &mut Frame<B>) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(52), Constraint::Min(20)].as_ref())
+        .split(*chunk);
+    let height = oam::sprite_height(memory);
+    let ly = oam::current_line(memory);
+    let dropped = oam::dropped(memory, ly);
+
+    let rows: Vec<ListItem> = (0..SPRITE_COUNT)
+        .map(|i| {
+            let sprite = Sprite::read(memory, i);
+            let flag = |set: bool, name: &'static str| if set { name } else { "-" };
+            let style = if dropped.contains(&i) {
+                Style::default().fg(Color::Red)
+            } else if sprite.is_offscreen(height) {
+                Style::default().fg(Color::DarkGray)
+            } else {
+                Style::default()
+            };
+            ListItem::new(Span::styled(
+                format!(
+                    "{:>2} ${:04X} {:>3} {:>3}  ${:02X}  {}{}{} {} bank{} pal{}",
+                    i, oam::OAM + i * 4, sprite.y, sprite.x, sprite.tile,
+                    flag(sprite.behind_background(), "P"),
+                    flag(sprite.y_flip(), "Y"),
+                    flag(sprite.x_flip(), "X"),
+                    sprite.palette(),
+                    sprite.cgb_bank(),
+                    sprite.cgb_palette(),
+                ),
+                style,
+            ))
+        })
+        .collect();
+    let list = List::new(rows)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(format!("OAM 8x{} LY {} [Up/Down] - select", height, ly))
+                .border_type(BorderType::Plain),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default();
+    state.select(Some(selected));
+    be.render_stateful_widget(list, chunks[0], &mut state);
+
+    let block = Block::default()
+        .title("Sprites")
+        .borders(Borders::ALL);
+    let area = block.inner(chunks[1]);
+    be.render_widget(block, chunks[1]);
+    be.render_widget(SpriteGrid { memory, selected }, area);
+}
@@ -0,0 +1,560 @@
+use std::{collections::{BTreeMap, BTreeSet, VecDeque}, fmt, ops::Range};
+use indexmap::IndexMap;
+
+pub mod rgbds;
+pub mod symbols;
+
+use crate::core::{
+    memory::{Memory, ROM_BANK_SIZE},
+    opcodes::{Instruction, InstrucionTarget},
+    info::InstructionInfo,
+    decoder::{build_table, build_prefix, TABLE_SIZE},
+};
+
+/// RST vectors, interrupt vectors and cartridge entry point
+pub const ENTRY_POINTS: [u16; 14] = [
+    0x0000, 0x0008, 0x0010, 0x0018, 0x0020, 0x0028, 0x0030, 0x0038,
+    0x0040, 0x0048, 0x0050, 0x0058, 0x0060,
+    0x0100,
+];
+
+/// Cartridge header(logo, title, checksums...), never decoded as code
+pub const HEADER: Range<u16> = 0x0104..0x0150;
+
+/// Max amount of bytes in a single data line
+const DATA_LINE_SIZE: u16 = 8;
+
+/// Bank-qualified address. Bank is meaningful only for switchable ROM(0x4000-0x7FFF)
+/// and is 0 everywhere else
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Location {
+    pub bank: u16,
+    pub addr: u16,
+}
+
+impl Location {
+    pub fn new(bank: u16, addr: u16) -> Location {
+        Location { bank, addr }
+    }
+
+    /// Location with bank dropped for addresses outside of switchable ROM
+    pub fn normalized(bank: u16, addr: u16) -> Location {
+        match addr {
+            0x4000..=0x7FFF => Location::new(bank, addr),
+            _ => Location::new(0, addr),
+        }
+    }
+
+    /// Location `addr` points to with banks currently mapped in `memory`
+    pub fn mapped(memory: &Memory, addr: u16) -> Location {
+        Location::normalized(memory.rom_bank(), addr)
+    }
+
+    fn offset(self, n: u16) -> Option<Location> {
+        self.addr.checked_add(n).map(|addr| Location::new(self.bank, addr))
+    }
+}
+
+/// Formats location as `BB:AAAA`, the same way symbol files do
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02X}:{:04X}", self.bank, self.addr)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum LineKind {
+    Code(InstructionInfo),
+    Data,
+}
+
+/// Single line of disassembly - an instruction or a run of data bytes
+#[derive(Clone, Debug)]
+pub struct Line {
+    pub location: Location,
+    pub bytes: Vec<u8>,
+    pub kind: LineKind,
+
+    /// Immediate operand, absolute destination for relative jumps
+    pub operand: Option<u16>,
+}
+
+impl Line {
+    pub fn is_code(&self) -> bool {
+        matches!(self.kind, LineKind::Code(_))
+    }
+
+    /// Address control is transferred to, if it is known statically
+    pub fn target(&self) -> Option<u16> {
+        match self.kind {
+            LineKind::Code(info) => match info.instruction {
+                Instruction::JP(_, InstrucionTarget::U16) |
+                Instruction::JR(_, _) |
+                Instruction::CALL(_, _) => self.operand,
+                Instruction::RST(offset) => Some(offset),
+                _ => None,
+            },
+            LineKind::Data => None,
+        }
+    }
+
+    /// Returns true if execution can continue with the next line
+    pub fn falls_through(&self) -> bool {
+        match self.kind {
+            LineKind::Code(info) => match info.instruction {
+                Instruction::JP(cond, _) |
+                Instruction::JR(cond, _) => !matches!(cond, InstrucionTarget::Blank),
+                Instruction::RET(cond) => !matches!(cond, InstrucionTarget::Blank),
+                Instruction::RETI | Instruction::XXX => false,
+                _ => true,
+            },
+            LineKind::Data => false,
+        }
+    }
+
+    /// Memory address the operand refers to - jump destination or memory operand
+    pub fn address(&self) -> Option<u16> {
+        match self.kind {
+            LineKind::Code(info) => match info.instruction {
+                Instruction::LD(InstrucionTarget::A8, _) |
+                Instruction::LD(_, InstrucionTarget::A8) => self.operand.map(|v| 0xFF00 | v),
+                Instruction::LD(InstrucionTarget::A16, _) |
+                Instruction::LD(_, InstrucionTarget::A16) => self.operand,
+                _ => self.target(),
+            },
+            LineKind::Data => None,
+        }
+    }
+
+    /// Same as `text`, but the address operand is replaced with its label if `symbol` knows it
+    pub fn text_with<'s>(&self, symbol: impl Fn(u16) -> Option<&'s str>) -> String {
+        let (info, name) = match (self.kind, self.address().and_then(symbol)) {
+            (LineKind::Code(info), Some(name)) => (info, name),
+            _ => return self.text(),
+        };
+        match info.instruction {
+            // RST vector is a part of the opcode
+            Instruction::RST(_) => self.text(),
+            _ => info.instruction
+                .to_string()
+                .replace("[$FF00+u8]", &format!("[{}]", name))
+                .replace("a16", &format!("[{}]", name))
+                .replace("u16", name)
+                .replace("i8", name),
+        }
+    }
+
+    /// Text of the line in `Instruction::to_string` syntax with operands substituted
+    pub fn text(&self) -> String {
+        match self.kind {
+            LineKind::Code(info) => format_instruction(info, self.operand),
+            LineKind::Data => {
+                let bytes: Vec<String> = self.bytes.iter().map(|b| format!("${:02X}", b)).collect();
+                format!("DB {}", bytes.join(" "))
+            },
+        }
+    }
+}
+
+/// Substitutes operand placeholders of `Instruction::to_string` with `operand`
+pub fn format_instruction(info: InstructionInfo, operand: Option<u16>) -> String {
+    let inst = info.instruction.to_string();
+    let v = match operand {
+        Some(v) => v,
+        None => return inst,
+    };
+    match info.instruction {
+        Instruction::JR(_, _) => inst.replace("i8", &format!("${:04X}", v)),
+        _ => inst
+            .replace("u16", &format!("${:04X}", v))
+            .replace("a16", &format!("[${:04X}]", v))
+            .replace("u8", &format!("${:02X}", v))
+            .replace("i8", &format!("${:02X}", v)),
+    }
+}
+
+/// Disassembled address range
+#[derive(Clone, Debug, Default)]
+pub struct Listing {
+    /// ROM bank the listing was built for
+    pub bank: u16,
+
+    /// Lines ordered by address
+    pub lines: IndexMap<u16, Line>,
+}
+
+impl Listing {
+    pub fn get(&self, addr: u16) -> Option<&Line> {
+        self.lines.get(&addr)
+    }
+
+    pub fn index_of(&self, addr: u16) -> Option<usize> {
+        self.lines.get_index_of(&addr)
+    }
+}
+
+/// Address space being disassembled
+trait Space {
+    /// Reads byte at `loc`, `None` if the location is outside of the space
+    fn read(&self, loc: Location) -> Option<u8>;
+
+    /// Resolves jump target from code at `from`, `None` if it can't be known statically
+    fn resolve(&self, from: Location, target: u16) -> Option<Location>;
+}
+
+/// Cartridge image split into banks, only ROM addresses(0x0000-0x7FFF) are accessible
+struct RomSpace<'a> {
+    rom: &'a [u8],
+}
+
+impl RomSpace<'_> {
+    fn banks(&self) -> u16 {
+        self.rom.len().div_ceil(ROM_BANK_SIZE).max(1) as u16
+    }
+}
+
+impl Space for RomSpace<'_> {
+    fn read(&self, loc: Location) -> Option<u8> {
+        let offset = match (loc.bank, loc.addr) {
+            (0, 0x0000..=0x3FFF) => loc.addr as usize,
+            (1..=0xFFFF, 0x4000..=0x7FFF) => loc.bank as usize * ROM_BANK_SIZE + (loc.addr as usize - 0x4000),
+            _ => return None,
+        };
+        self.rom.get(offset).copied()
+    }
+
+    fn resolve(&self, from: Location, target: u16) -> Option<Location> {
+        match target {
+            0x0000..=0x3FFF => Some(Location::new(0, target)),
+            0x4000..=0x7FFF => match from.bank {
+                // without a mapper bank 1 is always there, otherwise it depends on what was switched in
+                0 if self.banks() == 2 => Some(Location::new(1, target)),
+                0 => None,
+                bank => Some(Location::new(bank, target)),
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Address space as currently mapped in `Memory`
+struct MemorySpace<'a> {
+    memory: &'a Memory,
+}
+
+impl MemorySpace<'_> {
+    fn location(&self, addr: u16) -> Location {
+        Location::mapped(self.memory, addr)
+    }
+}
+
+impl Space for MemorySpace<'_> {
+    fn read(&self, loc: Location) -> Option<u8> {
+        Some(self.memory.peek(loc.addr as usize))
+    }
+
+    fn resolve(&self, _from: Location, target: u16) -> Option<Location> {
+        Some(self.location(target))
+    }
+}
+
+/// ## Control-flow-aware disassembler
+/// Follows code reachable from entry points through JP/JR/CALL/RST targets and
+/// fallthrough. Bytes that are never reached are treated as data.
+pub struct Disassembler {
+    opcode_table: [InstructionInfo; TABLE_SIZE],
+    prefix_table: [InstructionInfo; TABLE_SIZE],
+}
+
+impl Default for Disassembler {
+    fn default() -> Self {
+        Disassembler::new()
+    }
+}
+
+impl Disassembler {
+    pub fn new() -> Disassembler {
+        Disassembler {
+            opcode_table: build_table(),
+            prefix_table: build_prefix(),
+        }
+    }
+
+    /// Disassembles every bank of cartridge image. Bank 0 is listed at 0x0000-0x3FFF,
+    /// the rest at 0x4000-0x7FFF. Code in switchable banks is only reachable from bank 0
+    /// when the cartridge has no mapper, `entries` can be used to point at the rest.
+    pub fn disassemble_rom(&self, rom: &[u8], entries: &[Location]) -> Vec<Listing> {
+        let space = RomSpace { rom };
+        let mut roots: Vec<Location> = ENTRY_POINTS.iter().map(|a| Location::new(0, *a)).collect();
+        roots.extend_from_slice(entries);
+        let code = self.trace(&space, roots);
+
+        (0..space.banks())
+            .map(|bank| {
+                let window = if bank == 0 { 0x0000..0x4000 } else { 0x4000..0x8000 };
+                let locations = window.map(|addr| Location::new(bank, addr));
+                self.listing(&space, bank, locations, &code)
+            })
+            .collect()
+    }
+
+    /// Disassembles the whole address space as it is currently mapped.
+    /// `entries` are extra entry points, e.g. code in RAM
+    pub fn disassemble_memory(&self, memory: &Memory, entries: &[u16]) -> Listing {
+        let space = MemorySpace { memory };
+        let roots = ENTRY_POINTS.iter().chain(entries.iter()).map(|a| space.location(*a)).collect();
+        let code = self.trace(&space, roots);
+
+        let locations = (0..=0xFFFFu16).map(|addr| space.location(addr));
+        self.listing(&space, memory.rom_bank(), locations, &code)
+    }
+
+    /// Decodes a single instruction from `bytes` placed at `location`, `None` if
+    /// there are not enough bytes
+    pub fn decode_bytes(&self, location: Location, bytes: &[u8]) -> Option<Line> {
+        let mut info = self.opcode_table[*bytes.first()? as usize];
+        if let Instruction::PREFIX = info.instruction {
+            info = self.prefix_table[*bytes.get(1)? as usize];
+        }
+        let bytes = bytes.get(..info.length as usize)?.to_vec();
+        Some(Line {
+            location,
+            operand: operand(info, location.addr, &bytes),
+            kind: LineKind::Code(info),
+            bytes,
+        })
+    }
+
+    /// Decodes instruction at `loc`, `None` if it doesn't fit into the space
+    fn decode(&self, space: &dyn Space, loc: Location) -> Option<(InstructionInfo, Vec<u8>)> {
+        let op = space.read(loc)?;
+        let mut info = self.opcode_table[op as usize];
+        if let Instruction::PREFIX = info.instruction {
+            info = self.prefix_table[space.read(loc.offset(1)?)? as usize];
+        }
+        let bytes = (0..info.length)
+            .map(|i| space.read(loc.offset(i)?))
+            .collect::<Option<Vec<u8>>>()?;
+        Some((info, bytes))
+    }
+
+    /// Finds every instruction reachable from `roots`
+    fn trace(&self, space: &dyn Space, roots: Vec<Location>) -> BTreeMap<Location, Line> {
+        let mut code: BTreeMap<Location, Line> = BTreeMap::new();
+        // every byte covered by decoded instructions
+        let mut covered: BTreeSet<Location> = BTreeSet::new();
+        let mut queue: VecDeque<Location> = roots.into_iter().collect();
+
+        while let Some(start) = queue.pop_front() {
+            let mut loc = start;
+            loop {
+                if code.contains_key(&loc) || covered.contains(&loc) {
+                    break;
+                }
+                if loc.bank == 0 && HEADER.contains(&loc.addr) {
+                    break;
+                }
+                let (info, bytes) = match self.decode(space, loc) {
+                    Some(decoded) => decoded,
+                    None => break,
+                };
+                if let Instruction::XXX = info.instruction {
+                    break;
+                }
+                // instruction overlapping already decoded code means we are misaligned
+                let len = bytes.len() as u16;
+                let overlaps = (1..len).any(|i| match loc.offset(i) {
+                    Some(l) => covered.contains(&l),
+                    None => true,
+                });
+                if overlaps {
+                    break;
+                }
+
+                let line = Line {
+                    location: loc,
+                    operand: operand(info, loc.addr, &bytes),
+                    bytes,
+                    kind: LineKind::Code(info),
+                };
+                if let Some(target) = line.target().and_then(|t| space.resolve(loc, t)) {
+                    queue.push_back(target);
+                }
+                let falls_through = line.falls_through();
+                for i in 0..len {
+                    covered.insert(loc.offset(i).unwrap());
+                }
+                code.insert(loc, line);
+
+                match loc.offset(len) {
+                    Some(next) if falls_through => loc = next,
+                    _ => break,
+                }
+            }
+        }
+        code
+    }
+
+    /// Builds listing of `locations` from decoded `code`, everything else becomes data
+    fn listing(
+        &self,
+        space: &dyn Space,
+        bank: u16,
+        locations: impl Iterator<Item = Location>,
+        code: &BTreeMap<Location, Line>,
+    ) -> Listing {
+        let targets: BTreeSet<Location> = code
+            .values()
+            .filter_map(|l| l.target().and_then(|t| space.resolve(l.location, t)))
+            .collect();
+
+        let mut listing = Listing { bank, lines: IndexMap::new() };
+        let mut data: Option<Line> = None;
+        let mut skip_until: u32 = 0;
+
+        for loc in locations {
+            if (loc.addr as u32) < skip_until {
+                continue;
+            }
+            let line_start = code.contains_key(&loc)
+                || targets.contains(&loc)
+                || loc.addr % DATA_LINE_SIZE == 0
+                || (loc.bank == 0 && (loc.addr == HEADER.start || loc.addr == HEADER.end));
+            if line_start {
+                if let Some(line) = data.take() {
+                    listing.lines.insert(line.location.addr, line);
+                }
+            }
+            if let Some(line) = code.get(&loc) {
+                skip_until = loc.addr as u32 + line.bytes.len() as u32;
+                listing.lines.insert(loc.addr, line.clone());
+                continue;
+            }
+            let byte = match space.read(loc) {
+                Some(byte) => byte,
+                None => continue,
+            };
+            data.get_or_insert_with(|| Line {
+                location: loc,
+                bytes: Vec::new(),
+                kind: LineKind::Data,
+                operand: None,
+            }).bytes.push(byte);
+        }
+        if let Some(line) = data.take() {
+            listing.lines.insert(line.location.addr, line);
+        }
+        listing
+    }
+}
+
+/// Extracts immediate operand of instruction at `addr` from its `bytes`
+fn operand(info: InstructionInfo, addr: u16, bytes: &[u8]) -> Option<u16> {
+    match info.instruction {
+        Instruction::PREFIX | Instruction::STOP => None,
+        Instruction::JR(_, _) => {
+            let offset = bytes[1] as i8;
+            Some(addr.wrapping_add(bytes.len() as u16).wrapping_add(offset as u16))
+        },
+        // CB-prefixed instructions have no operands
+        _ if bytes[0] == 0xCB => None,
+        _ => match bytes.len() {
+            2 => Some(bytes[1] as u16),
+            3 => Some(u16::from_le_bytes([bytes[1], bytes[2]])),
+            _ => None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 32KiB cartridge with `code` placed at `addr`
+    fn rom_with(code: &[(u16, &[u8])]) -> Vec<u8> {
+        let mut rom = vec![0xFFu8; 0x8000];
+        for (addr, bytes) in code {
+            rom[*addr as usize..*addr as usize + bytes.len()].copy_from_slice(bytes);
+        }
+        rom
+    }
+
+    #[test]
+    fn test_decode_bytes() {
+        let disasm = Disassembler::new();
+        let at = Location::new(0, 0x0150);
+        assert_eq!(disasm.decode_bytes(at, &[0x18, 0xFE, 0x00]).unwrap().text(), "JR $0150");
+        assert_eq!(disasm.decode_bytes(at, &[0xCB, 0x7C]).unwrap().text(), "BIT 7 H");
+        assert_eq!(disasm.decode_bytes(at, &[0xC3, 0x50, 0x01, 0x00]).unwrap().bytes.len(), 3);
+        assert!(disasm.decode_bytes(at, &[0xC3, 0x50]).is_none());
+    }
+
+    #[test]
+    fn test_follows_control_flow() {
+        let rom = rom_with(&[
+            // NOP; JP $0150
+            (0x0100, &[0x00, 0xC3, 0x50, 0x01]),
+            // CALL $4000; JR $0150
+            (0x0150, &[0xCD, 0x00, 0x40, 0x18, 0xFB]),
+            // BIT 7 H; RET
+            (0x4000, &[0xCB, 0x7C, 0xC9]),
+        ]);
+        let listings = Disassembler::new().disassemble_rom(&rom, &[]);
+        assert_eq!(listings.len(), 2);
+
+        let bank0 = &listings[0];
+        assert_eq!(bank0.get(0x0101).unwrap().text(), "JP $0150");
+        assert_eq!(bank0.get(0x0150).unwrap().text(), "CALL $4000");
+        assert_eq!(bank0.get(0x0153).unwrap().text(), "JR $0150");
+        // header is data even though it directly follows code
+        assert!(!bank0.get(0x0104).unwrap().is_code());
+        // unreachable bytes after JR are data
+        assert!(!bank0.get(0x0155).unwrap().is_code());
+
+        let bank1 = &listings[1];
+        assert_eq!(bank1.get(0x4000).unwrap().text(), "BIT 7 H");
+        assert_eq!(bank1.get(0x4002).unwrap().text(), "RET");
+        assert!(!bank1.get(0x4003).unwrap().is_code());
+    }
+
+    #[test]
+    fn test_banked_code_needs_entries() {
+        let mut rom = rom_with(&[(0x0100, &[0xCD, 0x00, 0x40, 0x76])]);
+        rom.resize(0x10000, 0xFF);
+        rom[0x8000] = 0xC9;
+        let dis = Disassembler::new();
+
+        let listings = dis.disassemble_rom(&rom, &[]);
+        assert_eq!(listings.len(), 4);
+        assert!(!listings[2].get(0x4000).unwrap().is_code());
+
+        let listings = dis.disassemble_rom(&rom, &[Location::new(2, 0x4000)]);
+        assert_eq!(listings[2].get(0x4000).unwrap().text(), "RET");
+        assert_eq!(listings[2].get(0x4000).unwrap().location, Location::new(2, 0x4000));
+    }
+
+    #[test]
+    fn test_memory_view() {
+        let mut mem = Memory::new();
+        // JP $C000
+        mem[0x0100u16] = 0xC3;
+        mem[0x0101u16] = 0x00;
+        mem[0x0102u16] = 0xC0;
+        // LD A [$FF00+$44]; ADD SP $FE
+        for (i, b) in [0xF0, 0x44, 0xE8, 0xFE].iter().enumerate() {
+            mem[0xC000 + i] = *b;
+        }
+        let listing = Disassembler::new().disassemble_memory(&mem, &[]);
+        assert_eq!(listing.get(0xC000).unwrap().text(), "LD A [$FF00+$44]");
+        assert_eq!(listing.get(0xC002).unwrap().text(), "ADD SP $FE");
+        assert!(listing.get(0xFFF8).is_some());
+
+        let mut symbols = symbols::Symbols::new();
+        symbols.insert(Location::new(0, 0xC000), "Loop");
+        symbols.insert(Location::new(0, 0xFF44), "rLY");
+        let name = |addr| symbols.name(Location::mapped(&mem, addr));
+        assert_eq!(listing.get(0x0100).unwrap().text_with(name), "JP Loop");
+        assert_eq!(listing.get(0xC000).unwrap().text_with(name), "LD A [rLY]");
+        assert_eq!(listing.get(0xC002).unwrap().text_with(name), "ADD SP $FE");
+    }
+}
@@ -0,0 +1,328 @@
+use std::{collections::BTreeMap, fs, io, path::{Path, PathBuf}};
+
+use crate::core::opcodes::{Instruction, InstrucionTarget};
+use super::{Line, LineKind, Listing, Location, RomSpace, Space, HEADER};
+
+/*
+    RGBDS source export
+
+    Every ROM bank is written to its own file(`bank_000.asm`, `bank_001.asm`, ...) with
+    a fixed-address SECTION, so the ROM can be rebuilt with
+
+        rgbasm -o bank_000.o bank_000.asm
+        ...
+        rgblink -o rom.gb bank_*.o
+
+    Jump and call targets get exported labels(`Call_000_0150::`), so banks can refer to
+    each other. Instructions that rgbasm could encode differently are emitted as `db`
+    to keep the output byte-identical.
+*/
+
+/// Kind of label, in order of precedence when a location is targeted by several instructions
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum LabelKind {
+    Relative,
+    Jump,
+    Call,
+}
+
+/// Writes one `.asm` file per bank of `listings` into `dir`, returns paths of written files
+pub fn export(rom: &[u8], listings: &[Listing], dir: &Path) -> io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir)?;
+    let labels = labels(rom, listings);
+    listings
+        .iter()
+        .map(|listing| {
+            let path = dir.join(format!("bank_{:03X}.asm", listing.bank));
+            fs::write(&path, source(rom, listing, &labels))?;
+            Ok(path)
+        })
+        .collect()
+}
+
+/// Names of jump and call targets that start a line of disassembly
+fn labels(rom: &[u8], listings: &[Listing]) -> BTreeMap<Location, String> {
+    let space = RomSpace { rom };
+    let mut kinds: BTreeMap<Location, LabelKind> = BTreeMap::new();
+    for line in listings.iter().flat_map(|l| l.lines.values()) {
+        let kind = match line.kind {
+            LineKind::Code(info) => match info.instruction {
+                Instruction::JR(_, _) => LabelKind::Relative,
+                Instruction::JP(_, _) => LabelKind::Jump,
+                Instruction::CALL(_, _) => LabelKind::Call,
+                _ => continue,
+            },
+            LineKind::Data => continue,
+        };
+        let target = match line.target().and_then(|t| space.resolve(line.location, t)) {
+            Some(target) => target,
+            None => continue,
+        };
+        let starts_line = listings
+            .get(target.bank as usize)
+            .and_then(|l| l.get(target.addr))
+            .is_some();
+        if starts_line {
+            let entry = kinds.entry(target).or_insert(kind);
+            *entry = kind.max(*entry);
+        }
+    }
+
+    kinds
+        .into_iter()
+        .map(|(loc, kind)| {
+            let prefix = match kind {
+                LabelKind::Relative => "jr",
+                LabelKind::Jump => "Jump",
+                LabelKind::Call => "Call",
+            };
+            (loc, format!("{}_{:03X}_{:04X}", prefix, loc.bank, loc.addr))
+        })
+        .collect()
+}
+
+/// Assembly source of a single bank
+fn source(rom: &[u8], listing: &Listing, labels: &BTreeMap<Location, String>) -> String {
+    let space = RomSpace { rom };
+    let mut out = String::new();
+    if listing.bank == 0 {
+        out.push_str("SECTION \"ROM Bank $000\", ROM0[$0000]\n");
+    } else {
+        out.push_str(&format!(
+            "SECTION \"ROM Bank ${0:03X}\", ROMX[$4000], BANK[${0:03X}]\n",
+            listing.bank
+        ));
+    }
+
+    for line in listing.lines.values() {
+        let loc = line.location;
+        if loc.bank == 0 && loc.addr == HEADER.start {
+            out.push_str("\n; Cartridge header\n");
+        }
+        if let Some(label) = labels.get(&loc) {
+            out.push_str(&format!("\n{}::\n", label));
+        }
+
+        let target = line
+            .target()
+            .and_then(|t| space.resolve(loc, t))
+            .and_then(|t| labels.get(&t));
+        match instruction(line, target) {
+            Some(text) => out.push_str(&format!("    {}\n", text)),
+            None => out.push_str(&format!("    {}\n", db(&line.bytes))),
+        }
+    }
+    out
+}
+
+fn db(bytes: &[u8]) -> String {
+    let bytes: Vec<String> = bytes.iter().map(|b| format!("${:02X}", b)).collect();
+    format!("db {}", bytes.join(", "))
+}
+
+/// `i8` operand as signed decimal
+fn signed(v: u16) -> String {
+    format!("{}", v as u8 as i8)
+}
+
+/// Operand in rgbasm syntax, `imm` is the immediate operand of the line
+fn operand(target: InstrucionTarget, imm: Option<u16>) -> String {
+    let imm = imm.unwrap_or(0);
+    match target {
+        InstrucionTarget::A => "a".to_string(),
+        InstrucionTarget::B => "b".to_string(),
+        InstrucionTarget::C |
+        InstrucionTarget::CCond => "c".to_string(),
+        InstrucionTarget::D => "d".to_string(),
+        InstrucionTarget::E => "e".to_string(),
+        InstrucionTarget::H => "h".to_string(),
+        InstrucionTarget::L => "l".to_string(),
+        InstrucionTarget::AF => "af".to_string(),
+        InstrucionTarget::BC => "bc".to_string(),
+        InstrucionTarget::DE => "de".to_string(),
+        InstrucionTarget::HL => "hl".to_string(),
+        InstrucionTarget::SP => "sp".to_string(),
+        InstrucionTarget::HLMem => "[hl]".to_string(),
+        InstrucionTarget::BCMem => "[bc]".to_string(),
+        InstrucionTarget::DEMem => "[de]".to_string(),
+        InstrucionTarget::CMem => "[c]".to_string(),
+        InstrucionTarget::ZCond => "z".to_string(),
+        InstrucionTarget::NZCond => "nz".to_string(),
+        InstrucionTarget::NCCond => "nc".to_string(),
+        InstrucionTarget::U8 => format!("${:02X}", imm),
+        InstrucionTarget::U16 => format!("${:04X}", imm),
+        InstrucionTarget::A8 => format!("[${:04X}]", 0xFF00 | imm),
+        InstrucionTarget::A16 => format!("[${:04X}]", imm),
+        InstrucionTarget::I8 => signed(imm),
+        InstrucionTarget::SPi8 => {
+            let offset = imm as u8 as i8;
+            if offset < 0 { format!("sp{}", offset) } else { format!("sp+{}", offset) }
+        },
+        InstrucionTarget::PC |
+        InstrucionTarget::Blank => String::new(),
+    }
+}
+
+fn binary(mnemonic: &str, to: InstrucionTarget, from: InstrucionTarget, imm: Option<u16>) -> String {
+    format!("{} {}, {}", mnemonic, operand(to, imm), operand(from, imm))
+}
+
+fn unary(mnemonic: &str, op: InstrucionTarget, imm: Option<u16>) -> String {
+    format!("{} {}", mnemonic, operand(op, imm))
+}
+
+/// Jump with optional condition, `dest` is the label or address
+fn jump(mnemonic: &str, cond: InstrucionTarget, dest: String) -> String {
+    match cond {
+        InstrucionTarget::Blank => format!("{} {}", mnemonic, dest),
+        _ => format!("{} {}, {}", mnemonic, operand(cond, None), dest),
+    }
+}
+
+/// Text of the line in rgbasm syntax, `None` if it must be emitted as `db`
+fn instruction(line: &Line, label: Option<&String>) -> Option<String> {
+    let info = match line.kind {
+        LineKind::Code(info) => info,
+        LineKind::Data => return None,
+    };
+    let imm = line.operand;
+    let dest = || label.cloned().unwrap_or_else(|| format!("${:04X}", imm.unwrap_or(0)));
+    let text = match info.instruction {
+        // rgbasm may encode these as `ldh`
+        Instruction::LD(InstrucionTarget::A16, InstrucionTarget::A) |
+        Instruction::LD(InstrucionTarget::A, InstrucionTarget::A16) if imm? >= 0xFF00 => return None,
+        Instruction::LD(InstrucionTarget::A8, from) => binary("ldh", InstrucionTarget::A8, from, imm),
+        Instruction::LD(to, InstrucionTarget::A8) => binary("ldh", to, InstrucionTarget::A8, imm),
+        Instruction::LD(InstrucionTarget::CMem, from) => binary("ldh", InstrucionTarget::CMem, from, imm),
+        Instruction::LD(to, InstrucionTarget::CMem) => binary("ldh", to, InstrucionTarget::CMem, imm),
+        Instruction::LD(to, from) => binary("ld", to, from, imm),
+        Instruction::LDI(InstrucionTarget::HLMem, from) => format!("ld [hl+], {}", operand(from, imm)),
+        Instruction::LDI(to, _) => format!("ld {}, [hl+]", operand(to, imm)),
+        Instruction::LDD(InstrucionTarget::HLMem, from) => format!("ld [hl-], {}", operand(from, imm)),
+        Instruction::LDD(to, _) => format!("ld {}, [hl-]", operand(to, imm)),
+        Instruction::PUSH(op) => unary("push", op, imm),
+        Instruction::POP(op) => unary("pop", op, imm),
+        Instruction::ADD(to, from) => binary("add", to, from, imm),
+        Instruction::ADC(to, from) => binary("adc", to, from, imm),
+        Instruction::SBC(to, from) => binary("sbc", to, from, imm),
+        Instruction::SUB(_, from) => unary("sub", from, imm),
+        Instruction::AND(_, from) => unary("and", from, imm),
+        Instruction::XOR(_, from) => unary("xor", from, imm),
+        Instruction::OR(_, from) => unary("or", from, imm),
+        Instruction::CP(_, from) => unary("cp", from, imm),
+        Instruction::INC(op) => unary("inc", op, imm),
+        Instruction::DEC(op) => unary("dec", op, imm),
+        Instruction::RLC(op) => unary("rlc", op, imm),
+        Instruction::RL(op) => unary("rl", op, imm),
+        Instruction::RRC(op) => unary("rrc", op, imm),
+        Instruction::RR(op) => unary("rr", op, imm),
+        Instruction::SLA(op) => unary("sla", op, imm),
+        Instruction::SWAP(op) => unary("swap", op, imm),
+        Instruction::SRA(op) => unary("sra", op, imm),
+        Instruction::SRL(op) => unary("srl", op, imm),
+        Instruction::BIT(bit, op) => format!("bit {}, {}", bit, operand(op, imm)),
+        Instruction::SET(bit, op) => format!("set {}, {}", bit, operand(op, imm)),
+        Instruction::RES(bit, op) => format!("res {}, {}", bit, operand(op, imm)),
+        Instruction::DAA => "daa".to_string(),
+        Instruction::CPL => "cpl".to_string(),
+        Instruction::RLCA => "rlca".to_string(),
+        Instruction::RLA => "rla".to_string(),
+        Instruction::RRCA => "rrca".to_string(),
+        Instruction::RRA => "rra".to_string(),
+        Instruction::CCF => "ccf".to_string(),
+        Instruction::SCF => "scf".to_string(),
+        Instruction::NOP => "nop".to_string(),
+        Instruction::HALT => "halt".to_string(),
+        // rgbasm always emits `$10 $00`
+        Instruction::STOP if line.bytes[1] != 0x00 => return None,
+        Instruction::STOP => "stop".to_string(),
+        Instruction::DI => "di".to_string(),
+        Instruction::EI => "ei".to_string(),
+        Instruction::JP(_, InstrucionTarget::HL) => "jp hl".to_string(),
+        Instruction::JP(cond, _) => jump("jp", cond, dest()),
+        Instruction::JR(cond, _) => jump("jr", cond, dest()),
+        Instruction::CALL(cond, _) => jump("call", cond, dest()),
+        Instruction::RET(InstrucionTarget::Blank) => "ret".to_string(),
+        Instruction::RET(cond) => unary("ret", cond, imm),
+        Instruction::RETI => "reti".to_string(),
+        Instruction::RST(vector) => format!("rst ${:02X}", vector),
+        Instruction::PREFIX | Instruction::XXX => return None,
+    };
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{core::assembler::Assembler, disasm::Disassembler};
+
+    fn rom() -> Vec<u8> {
+        let mut rom = vec![0x00u8; 0x8000];
+        let code: &[(u16, &[u8])] = &[
+            // NOP; JP $0150
+            (0x0100, &[0x00, 0xC3, 0x50, 0x01]),
+            // title and header checksum
+            (0x0134, b"TEST"),
+            (0x014D, &[0xE7]),
+            // CALL $4000; LD [$FF40] A(long form); LDH [$FF44] A; JR $0150
+            (0x0150, &[0xCD, 0x00, 0x40, 0xEA, 0x40, 0xFF, 0xE0, 0x44, 0x18, 0xF6]),
+            // LD HL SP-2; ADD SP 5; LDI A [HL]; STOP $01; LDD [HL] A; LD A [$FF00+C]; RET NZ; RET
+            (0x4000, &[0xF8, 0xFE, 0xE8, 0x05, 0x2A, 0x10, 0x01, 0x32, 0xF2, 0xC0, 0xC9]),
+        ];
+        for (addr, bytes) in code {
+            rom[*addr as usize..*addr as usize + bytes.len()].copy_from_slice(bytes);
+        }
+        rom
+    }
+
+    /// Bytes of a bank source, instructions are encoded by the assembler with
+    /// labels replaced by their addresses
+    fn reassemble(source: &str, labels: &BTreeMap<Location, String>) -> Vec<u8> {
+        let asm = Assembler::new();
+        let origin = if source.contains("ROM0[$0000]") { 0x0000 } else { 0x4000 };
+        let mut out = Vec::new();
+        for line in source.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with(';') || line.starts_with("SECTION") || line.ends_with("::") {
+                continue;
+            }
+            if let Some(bytes) = line.strip_prefix("db ") {
+                out.extend(bytes.split(", ").map(|b| u8::from_str_radix(&b[1..], 16).unwrap()));
+                continue;
+            }
+            let text = labels
+                .iter()
+                .fold(line.to_string(), |text, (loc, name)| text.replace(name, &format!("${:04X}", loc.addr)));
+            let bytes = asm.assemble(&text, origin + out.len() as u16);
+            out.extend(bytes.unwrap_or_else(|e| panic!("{}: {}", line, e)));
+        }
+        out
+    }
+
+    #[test]
+    fn test_reassemble() {
+        let rom = rom();
+        let listings = Disassembler::new().disassemble_rom(&rom, &[]);
+        let labels = labels(&rom, &listings);
+        for (listing, bank) in listings.iter().zip(rom.chunks(0x4000)) {
+            let source = source(&rom, listing, &labels);
+            assert!(reassemble(&source, &labels) == bank, "bank {} differs", listing.bank);
+        }
+    }
+
+    #[test]
+    fn test_bank_source() {
+        let rom = rom();
+        let listings = Disassembler::new().disassemble_rom(&rom, &[]);
+        let labels = labels(&rom, &listings);
+
+        let bank0 = source(&rom, &listings[0], &labels);
+        assert!(bank0.starts_with("SECTION \"ROM Bank $000\", ROM0[$0000]\n"));
+        assert!(bank0.contains("    jp Jump_000_0150\n"));
+        assert!(bank0.contains("; Cartridge header\n    db $00"));
+        assert!(bank0.contains("\nJump_000_0150::\n    call Call_001_4000\n    db $EA, $40, $FF\n    ldh [$FF44], a\n    jr Jump_000_0150\n"));
+
+        let bank1 = source(&rom, &listings[1], &labels);
+        assert!(bank1.starts_with("SECTION \"ROM Bank $001\", ROMX[$4000], BANK[$001]\n"));
+        assert!(bank1.contains("\nCall_001_4000::\n    ld hl, sp-2\n    add sp, 5\n    ld a, [hl+]\n    db $10, $01\n    ld [hl-], a\n    ldh a, [c]\n    ret nz\n    ret\n"));
+    }
+}
@@ -0,0 +1,186 @@
+use std::{collections::{BTreeMap, HashMap}, fs, io, path::Path};
+
+use super::Location;
+
+/*
+    Symbol files
+
+    Both RGBDS(`rgblink -n`) and no$gmb symbol files list one label per line:
+
+        ; comment
+        00:0150 Main
+        01:4000 UpdateSprites
+
+    no$gmb files may be split into `[labels]`, `[definitions]`... sections,
+    only `[labels]` ones contain addresses. Lines that don't parse are skipped
+    with a warning, so one odd line doesn't lose the rest of the file.
+*/
+
+/// Parses `bank:addr label`
+fn parse_line(line: &str) -> Option<(Location, &str)> {
+    let mut parts = line.split_whitespace();
+    let (location, name) = (parts.next()?, parts.next()?);
+    let (bank, addr) = location.split_once(':')?;
+    let bank = u16::from_str_radix(bank, 16).ok()?;
+    let addr = u16::from_str_radix(addr, 16).ok()?;
+    Some((Location::normalized(bank, addr), name))
+}
+
+/// Table of label names by bank-qualified address
+#[derive(Clone, Debug, Default)]
+pub struct Symbols {
+    names: BTreeMap<Location, String>,
+    locations: HashMap<String, Location>,
+    /// Lines skipped while parsing, with the reason
+    warnings: Vec<String>,
+}
+
+impl Symbols {
+    pub fn new() -> Symbols {
+        Symbols::default()
+    }
+
+    /// Loads symbol file at `path`, a missing file gives an empty table
+    pub fn load(path: &Path) -> io::Result<Symbols> {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(Symbols::parse(&text)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Symbols::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn parse(text: &str) -> Symbols {
+        let mut symbols = Symbols::new();
+        let mut in_labels = true;
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('[') {
+                in_labels = line.eq_ignore_ascii_case("[labels]");
+                continue;
+            }
+            if !in_labels {
+                continue;
+            }
+            match parse_line(line) {
+                Some((location, name)) => symbols.insert(location, name),
+                None => symbols.warnings.push(format!("line {}: expected `bank:addr label`, got `{}`", n + 1, line)),
+            }
+        }
+        symbols
+    }
+
+    /// Lines skipped by `parse`
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    pub fn insert(&mut self, location: Location, name: &str) {
+        self.names.insert(location, name.to_string());
+        self.locations.insert(name.to_string(), location);
+    }
+
+    /// Label at `location`
+    pub fn name(&self, location: Location) -> Option<&str> {
+        self.names.get(&location).map(String::as_str)
+    }
+
+    /// Closest label at or before `location` in the same memory region, with
+    /// offset from it
+    pub fn containing(&self, location: Location) -> Option<(&str, u16)> {
+        // ROM banks are 16KiB, other areas 8KiB
+        let region = |addr: u16| if addr < 0x8000 { addr >> 14 } else { addr >> 13 };
+        let (start, name) = self.names.range(..=location).next_back()?;
+        if start.bank != location.bank || region(start.addr) != region(location.addr) {
+            return None;
+        }
+        Some((name, location.addr - start.addr))
+    }
+
+    /// Location of label `name`
+    pub fn location(&self, name: &str) -> Option<Location> {
+        self.locations.get(name).copied()
+    }
+
+    /// All label names
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.locations.keys().map(String::as_str)
+    }
+
+    /// Replaces label names in `text` with their addresses, e.g. `CALL UpdateSprites`
+    /// becomes `CALL $4000`
+    pub fn expand(&self, text: &str) -> String {
+        let mut out = String::new();
+        let mut word = String::new();
+        let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.';
+        for c in text.chars().chain(std::iter::once(' ')) {
+            if is_word(c) {
+                word.push(c);
+                continue;
+            }
+            match self.location(&word) {
+                Some(loc) if !word.is_empty() => out.push_str(&format!("${:04X}", loc.addr)),
+                _ => out.push_str(&word),
+            }
+            word.clear();
+            out.push(c);
+        }
+        out.pop();
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let symbols = Symbols::parse(
+            "; File generated by rgblink\n\
+             00:0150 Main\n\
+             01:4000 UpdateSprites ; comment\n\
+             05:C000 wCounter\n\
+             [definitions]\n\
+             00:0040 VBlankHandler\n",
+        );
+        assert_eq!(symbols.name(Location::new(0, 0x0150)), Some("Main"));
+        assert_eq!(symbols.location("UpdateSprites"), Some(Location::new(1, 0x4000)));
+        // banks of RAM are not tracked
+        assert_eq!(symbols.location("wCounter"), Some(Location::new(0, 0xC000)));
+        assert_eq!(symbols.location("VBlankHandler"), None);
+
+        assert!(symbols.warnings().is_empty());
+
+        // malformed lines are skipped, the rest still loads
+        let symbols = Symbols::parse("00:0150\n0150 Main\nGG:0150 Bad\n00:0200 Loop\n");
+        assert_eq!(symbols.location("Loop"), Some(Location::new(0, 0x0200)));
+        assert_eq!(symbols.warnings().len(), 3);
+        assert!(symbols.warnings()[1].starts_with("line 2:"));
+    }
+
+    #[test]
+    fn test_expand() {
+        let mut symbols = Symbols::new();
+        symbols.insert(Location::new(1, 0x4000), "UpdateSprites");
+        symbols.insert(Location::new(0, 0xC000), "wCounter");
+        assert_eq!(symbols.expand("CALL UpdateSprites"), "CALL $4000");
+        assert_eq!(symbols.expand("ld a, [wCounter]"), "ld a, [$C000]");
+        assert_eq!(symbols.expand("LD A B"), "LD A B");
+    }
+
+    #[test]
+    fn test_containing() {
+        let mut symbols = Symbols::new();
+        symbols.insert(Location::new(0, 0x0150), "Main");
+        symbols.insert(Location::new(1, 0x4000), "UpdateSprites");
+        assert_eq!(symbols.containing(Location::new(0, 0x0150)), Some(("Main", 0)));
+        assert_eq!(symbols.containing(Location::new(0, 0x3158)), Some(("Main", 0x3008)));
+        assert_eq!(symbols.containing(Location::new(1, 0x4010)), Some(("UpdateSprites", 0x10)));
+        assert_eq!(symbols.containing(Location::new(2, 0x4010)), None);
+        assert_eq!(symbols.containing(Location::new(0, 0x0100)), None);
+        assert_eq!(symbols.containing(Location::new(0, 0xC000)), None);
+    }
+}
@@ -1,18 +1,213 @@
-use std::path::Path;
+use std::{any::Any, env, error::Error, panic, path::PathBuf, process};
 
 mod cpu;
 mod core;
 mod debugger;
+mod disasm;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut cpu = cpu::CPU::new();
-    cpu.memory.load(&Path::new("D:\\utilities\\gb_roms\\games\\Tetris (World) (Rev A).gb"));
-    let mut dbg = debugger::Debugger::new(cpu);
-    dbg.run()
-    // cpu.check_table();
-    // while !cpu.stopped {
-    //     cpu.clock();
-    // }
-
-    // Ok(())
+const USAGE: &str = "\
+usage: gb-emu [options] <rom>
+
+options:
+    --boot-rom <file>           run boot ROM before the cartridge
+    --model <model>             hardware to emulate, only dmg for now
+    --debugger                  open the debugger, the default
+    --headless                  run without the debugger, needs --frames, --cycles or --compare
+    --frames <N>                run N frames, before the debugger opens unless headless
+    --cycles <N>                run N cycles, same as --frames
+    --trace <file>              write executed instructions in gameboy-doctor format
+    --trace-range <start>-<end> only trace instructions in the range
+    --trace-limit <N>           stop tracing after N lines
+    --compare <file>            run until state differs from a gameboy-doctor trace
+    --screenshot <file>         save the screen as PNG when a headless run ends
+    -h, --help                  show this help
+
+Exit status is 0 on success, 1 when emulation fails or the trace diverges,
+2 for invalid arguments.";
+
+/// Command line options
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Options {
+    rom: PathBuf,
+    boot_rom: Option<PathBuf>,
+    headless: bool,
+    /// Cycles to run, given as frames or cycles
+    cycles: Option<u64>,
+    trace: Option<String>,
+    /// Range and limit in `trace` command syntax
+    trace_options: Vec<String>,
+    compare: Option<String>,
+    screenshot: Option<PathBuf>,
+}
+
+/// Parses arguments following the program name, `None` when help was asked for
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options::default();
+    let mut rom = None;
+    let mut debugger = false;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} expects a value", arg));
+        let count = |value: String, scale: u64| value
+            .parse::<u64>()
+            .ok()
+            .and_then(|n| n.checked_mul(scale))
+            .ok_or_else(|| format!("invalid count {}", value));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--boot-rom" => options.boot_rom = Some(PathBuf::from(value()?)),
+            "--model" => match value()?.to_lowercase().as_str() {
+                "dmg" => {},
+                model => return Err(format!("model {} is not emulated, only dmg is", model)),
+            },
+            "--debugger" => debugger = true,
+            "--headless" => options.headless = true,
+            "--frames" | "--cycles" if options.cycles.is_some() => return Err("--frames and --cycles can't be combined".to_string()),
+            "--frames" => options.cycles = Some(count(value()?, debugger::FRAME_CYCLES as u64)?),
+            "--cycles" => options.cycles = Some(count(value()?, 1)?),
+            "--trace" => options.trace = Some(value()?),
+            "--trace-range" => options.trace_options.push(value()?),
+            "--trace-limit" => options.trace_options.push(format!("limit {}", value()?)),
+            "--compare" => options.compare = Some(value()?),
+            "--screenshot" => options.screenshot = Some(PathBuf::from(value()?)),
+            _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+            _ if rom.is_some() => return Err(format!("unexpected argument {}", arg)),
+            _ => rom = Some(PathBuf::from(arg)),
+        }
+    }
+    options.rom = rom.ok_or("missing ROM path")?;
+    if debugger && options.headless {
+        return Err("--debugger and --headless can't be combined".to_string());
+    }
+    if options.headless && options.cycles.is_none() && options.compare.is_none() {
+        return Err("--headless needs --frames, --cycles or --compare".to_string());
+    }
+    if options.trace.is_none() && !options.trace_options.is_empty() {
+        return Err("trace options require --trace <file>".to_string());
+    }
+    if options.screenshot.is_some() && !options.headless {
+        return Err("--screenshot needs --headless".to_string());
+    }
+    Ok(Some(options))
+}
+
+fn run(options: Options) -> Result<(), Box<dyn Error>> {
+    let rom = options.rom.as_path();
+    let mut dbg = debugger::Debugger::new(cpu::CPU::new());
+    dbg.load_rom(rom).map_err(|e| format!("{}: {}", rom.display(), e))?;
+    if let Some(path) = &options.boot_rom {
+        dbg.load_boot_rom(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    let warnings = dbg.load_symbols(&rom.with_extension("sym"))?;
+    if options.headless {
+        warnings.iter().for_each(|w| eprintln!("gb-emu: {}", w));
+    }
+    dbg.run_script(&rom.with_extension("dbg"))?;
+    if let Some(file) = &options.trace {
+        dbg.trace(&format!("{} {}", file, options.trace_options.join(" ")))?;
+    }
+
+    if !options.headless {
+        // results are shown in the console and status
+        if let Some(file) = &options.compare {
+            dbg.execute(&format!("compare {}", file));
+        }
+        if let Some(cycles) = options.cycles {
+            dbg.run_for(cycles);
+        }
+        return dbg.run();
+    }
+
+    let mut matched = true;
+    if let Some(file) = &options.compare {
+        let (result, output) = dbg.compare(file)?;
+        output.iter().for_each(|line| println!("{}", line));
+        matched = result;
+    }
+    if let (true, Some(cycles)) = (matched, options.cycles) {
+        println!("{}", dbg.run_for(cycles));
+    }
+    if options.trace.is_some() {
+        println!("{}", dbg.trace("off")?);
+    }
+    if let Some(path) = &options.screenshot {
+        dbg.screenshot(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        println!("Saved screenshot to {}", path.display());
+    }
+    if !matched {
+        return Err(format!("trace diverged from {}", options.compare.unwrap_or_default()).into());
+    }
+    Ok(())
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        },
+        Err(e) => {
+            eprintln!("gb-emu: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        },
+    };
+    let error = if options.headless {
+        // the CPU panics on illegal opcodes, scripts get it as a failure like any other
+        panic::set_hook(Box::new(|_| {}));
+        match panic::catch_unwind(|| run(options)) {
+            Ok(result) => result.err(),
+            Err(payload) => Some(format!("emulation failed: {}", panic_message(&*payload)).into()),
+        }
+    } else {
+        run(options).err()
+    };
+    if let Some(e) = error {
+        eprintln!("gb-emu: {}", e);
+        process::exit(1);
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload.downcast_ref::<String>().map(String::as_str)
+        .or_else(|| payload.downcast_ref::<&str>().copied())
+        .unwrap_or("unknown error")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn parse(args: &str) -> Result<Option<Options>, String> {
+        parse_args(args.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options = parse("--headless --frames 2 --trace out.log --trace-limit 10 game.gb").unwrap().unwrap();
+        assert_eq!(options.rom, Path::new("game.gb"));
+        assert!(options.headless);
+        assert_eq!(options.cycles, Some(2 * debugger::FRAME_CYCLES as u64));
+        assert_eq!(options.trace_options, vec!["limit 10"]);
+        assert_eq!(parse("--model DMG --debugger game.gb").unwrap().unwrap().cycles, None);
+        assert_eq!(parse("--help"), Ok(None));
+
+        assert!(parse("--frames 1").is_err());
+        assert!(parse("--model cgb game.gb").is_err());
+        assert!(parse("--headless game.gb").is_err());
+        assert!(parse("--headless --debugger --cycles 5 game.gb").is_err());
+        assert!(parse("--frames 1 --cycles 5 game.gb").is_err());
+        assert!(parse("--cycles x game.gb").is_err());
+        assert_eq!(parse("--frames 999999999999999999 game.gb"), Err("invalid count 999999999999999999".to_string()));
+        assert!(parse("--screenshot out.png game.gb").is_err());
+        assert_eq!(
+            parse("--headless --cycles 5 --screenshot out.png game.gb").unwrap().unwrap().screenshot,
+            Some(PathBuf::from("out.png")),
+        );
+        assert!(parse("--trace-range 0-1 game.gb").is_err());
+        assert!(parse("--frames").is_err());
+        assert!(parse("game.gb other.gb").is_err());
+    }
 }